@@ -0,0 +1,390 @@
+use crate::asteroid::Asteroid;
+use crate::bodies::*;
+use crate::bullet::CreateBullet;
+use crate::console::{Cheated, ConfigExt};
+use crate::control::{Pawn, PlayerController, ShipPawn};
+use crate::input_actions::*;
+use crate::powerup::Shielded;
+use crate::schedule::{resimulating, InGameSet};
+use crate::score::{Score, Scored};
+use crate::ship::{Invulnerable, Ship};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::time::Duration;
+
+// what's been unlocked so far, kept next to the savegame
+const UNLOCKED_PATH: &str = "unlocked.ron";
+// pixels between stacked toasts
+const TOAST_SPACING: f32 = 36.0;
+
+// all in one run
+#[derive(Deserialize, Debug, Clone, Copy)]
+enum Goal {
+    Points(u64),
+    Destroyed(u32),
+    Shots(u32),
+    // seconds
+    Survive(u64),
+}
+
+// doing any of these during the run rules an achievement out until the next one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rule {
+    Thrust,
+    Shoot,
+    Shield,
+    // an asteroid touching the ship without a shield in the way
+    Hit,
+}
+
+#[derive(Deserialize, Clone)]
+struct Achievement {
+    id: String,
+    name: String,
+    description: String,
+    goal: Goal,
+    #[serde(default)]
+    without: Vec<Rule>,
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct AchievementConfig {
+    toast_time: u64,
+    achievements: Vec<Achievement>,
+}
+
+#[derive(Resource)]
+struct AchievementConfigHandle {
+    config: Handle<AchievementConfig>,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+struct Unlocked {
+    ids: BTreeSet<String>,
+}
+
+// the local player's run so far, replays and bots don't unlock anything
+// saved and rolled back with the rest of the world
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RunProgress {
+    destroyed: u32,
+    shots: u32,
+    time: Duration,
+    broken: BTreeSet<Rule>,
+}
+
+#[derive(Resource, Default)]
+struct AchievementsScreen {
+    open: bool,
+}
+
+#[derive(Component)]
+struct AchievementsPanel;
+
+#[derive(Component)]
+struct Toast(Timer);
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.achievements.ron");
+    commands.insert_resource(AchievementConfigHandle { config });
+}
+
+fn load_unlocked(mut commands: Commands) {
+    let unlocked = std::fs::read_to_string(UNLOCKED_PATH)
+        .ok()
+        .and_then(|text| match ron::from_str::<Unlocked>(&text) {
+            Ok(unlocked) => Some(unlocked),
+            Err(e) => {
+                warn!("ignoring {}: {}", UNLOCKED_PATH, e);
+                None
+            }
+        })
+        .unwrap_or_default();
+    commands.insert_resource(unlocked);
+}
+
+fn save_unlocked(unlocked: &Unlocked) {
+    let result = ron::to_string(unlocked)
+        .map_err(|e| e.to_string())
+        .and_then(|text| std::fs::write(UNLOCKED_PATH, text).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("could not save achievements: {}", e);
+    }
+}
+
+fn watch_inputs(
+    mut progress: ResMut<RunProgress>,
+    mut events: EventReader<InputEvent>,
+    players: Query<(), With<PlayerController>>,
+) {
+    for event in events.read() {
+        if !players.contains(event.controller) {
+            continue;
+        }
+        match event.input.action {
+            Actions::MoveForward if event.input.value > 0.0 => {
+                progress.broken.insert(Rule::Thrust);
+            }
+            Actions::Shield => {
+                progress.broken.insert(Rule::Shield);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn watch_shots(
+    mut progress: ResMut<RunProgress>,
+    mut events: EventReader<CreateBullet>,
+    players: Query<(), With<PlayerController>>,
+) {
+    for event in events.read() {
+        if players.contains(event.shooter) {
+            progress.shots += 1;
+            progress.broken.insert(Rule::Shoot);
+        }
+    }
+}
+
+fn watch_scored(
+    mut progress: ResMut<RunProgress>,
+    mut events: EventReader<Scored>,
+    players: Query<(), With<PlayerController>>,
+) {
+    for event in events.read() {
+        if event.player.is_some_and(|player| players.contains(player)) {
+            progress.destroyed += 1;
+        }
+    }
+}
+
+type WatchedShip<'a> = (&'a ShipPawn, Has<Deflecting>, Has<Shielded>);
+
+fn watch_collisions(
+    mut progress: ResMut<RunProgress>,
+    mut collisions: EventReader<Collision>,
+    ships: Query<WatchedShip, With<Ship>>,
+    asteroids: Query<(), With<Asteroid>>,
+    players: Query<(), With<PlayerController>>,
+) {
+    for event in collisions.read() {
+        for (entity_a, entity_b) in [
+            (event.entity1, event.entity2),
+            (event.entity2, event.entity1),
+        ] {
+            let Ok((pawn, deflecting, shielded)) = ships.get(entity_a) else {
+                continue;
+            };
+            if asteroids.contains(entity_b)
+                && players.contains(*pawn.get_controller())
+                && !deflecting
+                && !shielded
+            {
+                progress.broken.insert(Rule::Hit);
+            }
+        }
+    }
+}
+
+fn watch_time(time: Res<Time>, mut progress: ResMut<RunProgress>) {
+    progress.time += time.delta();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn unlock(
+    mut commands: Commands,
+    progress: Res<RunProgress>,
+    cheated: Res<Cheated>,
+    score: Res<Score>,
+    players: Query<Entity, With<PlayerController>>,
+    mut unlocked: ResMut<Unlocked>,
+    toasts: Query<(), With<Toast>>,
+    configs: Res<Assets<AchievementConfig>>,
+    config_handle: Res<AchievementConfigHandle>,
+) {
+    if cheated.0 {
+        return;
+    }
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let points: u64 = players
+        .iter()
+        .filter_map(|player| score.players.get(&player))
+        .sum();
+
+    let mut shown = toasts.iter().count();
+    for achievement in config.achievements.iter() {
+        if unlocked.ids.contains(&achievement.id) {
+            continue;
+        }
+        if achievement
+            .without
+            .iter()
+            .any(|rule| progress.broken.contains(rule))
+        {
+            continue;
+        }
+        let reached = match achievement.goal {
+            Goal::Points(goal) => points >= goal,
+            Goal::Destroyed(goal) => progress.destroyed >= goal,
+            Goal::Shots(goal) => progress.shots >= goal,
+            Goal::Survive(goal) => progress.time.as_secs() >= goal,
+        };
+        if !reached {
+            continue;
+        }
+
+        info!("achievement unlocked: {}", achievement.name);
+        unlocked.ids.insert(achievement.id.clone());
+        save_unlocked(&unlocked);
+        commands.spawn((
+            Toast(Timer::new(
+                Duration::from_millis(config.toast_time),
+                TimerMode::Once,
+            )),
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(12.0),
+                top: Val::Px(12.0 + shown as f32 * TOAST_SPACING),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            GlobalZIndex(10),
+            Text::new(format!("Achievement unlocked: {}", achievement.name)),
+            TextFont {
+                font_size: 18.0,
+                ..default()
+            },
+        ));
+        shown += 1;
+    }
+}
+
+fn expire_toasts(mut commands: Commands, time: Res<Time>, mut toasts: Query<(Entity, &mut Toast)>) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn reset_progress(
+    mut progress: ResMut<RunProgress>,
+    mut cheated: ResMut<Cheated>,
+    invulnerable: Res<Invulnerable>,
+    time: Res<Time<Virtual>>,
+) {
+    *progress = RunProgress::default();
+    // god mode and a changed timescale carry over into the new run
+    cheated.0 = invulnerable.0 || time.relative_speed() != 1.0;
+}
+
+fn toggle_screen(mut screen: ResMut<AchievementsScreen>, mut events: EventReader<InputEvent>) {
+    for event in events.read() {
+        if event.input.action == Actions::ToggleAchievements {
+            screen.open = !screen.open;
+        }
+    }
+}
+
+fn show_screen(
+    mut commands: Commands,
+    screen: Res<AchievementsScreen>,
+    unlocked: Res<Unlocked>,
+    panels: Query<Entity, With<AchievementsPanel>>,
+    configs: Res<Assets<AchievementConfig>>,
+    config_handle: Res<AchievementConfigHandle>,
+) {
+    if !screen.is_changed() && !unlocked.is_changed() {
+        return;
+    }
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    if !screen.open {
+        return;
+    }
+
+    let mut shown = String::from("Achievements  F5 close\n\n");
+    match configs.get(config_handle.config.id()) {
+        Some(config) => {
+            let _ = writeln!(
+                shown,
+                "{} of {} unlocked\n",
+                config
+                    .achievements
+                    .iter()
+                    .filter(|achievement| unlocked.ids.contains(&achievement.id))
+                    .count(),
+                config.achievements.len()
+            );
+            for achievement in config.achievements.iter() {
+                let tick = if unlocked.ids.contains(&achievement.id) {
+                    "x"
+                } else {
+                    " "
+                };
+                let _ = writeln!(
+                    shown,
+                    "[{}] {} - {}",
+                    tick, achievement.name, achievement.description
+                );
+            }
+        }
+        None => shown.push_str("still loading"),
+    }
+
+    commands
+        .spawn((
+            AchievementsPanel,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(15.0),
+                top: Val::Percent(15.0),
+                width: Val::Percent(70.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.85)),
+            GlobalZIndex(15),
+        ))
+        .with_child((
+            Text::new(shown),
+            TextFont {
+                font_size: 18.0,
+                ..default()
+            },
+        ));
+}
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<AchievementConfig>(&["achievements.ron"]);
+        app.init_resource::<RunProgress>();
+        app.init_resource::<AchievementsScreen>();
+        app.add_systems(Startup, (load_config, load_unlocked));
+        app.add_systems(Update, toggle_screen.in_set(InGameSet::GameInput));
+        app.add_systems(Update, (watch_time).in_set(InGameSet::UpdateEntities));
+        app.add_systems(
+            Update,
+            (
+                (watch_inputs, watch_shots, watch_scored, watch_collisions),
+                // redone frames were already checked, and a rollback doesn't take back an unlock
+                unlock.run_if(not(resimulating)),
+            )
+                .chain()
+                .in_set(InGameSet::CollisionReaction),
+        );
+        app.add_systems(Update, (expire_toasts, show_screen));
+        // a new run, everything is back on the table
+        // a rollback that takes back a game over restores the progress after this
+        app.add_systems(OnExit(GameState::GameOver), reset_progress);
+    }
+}
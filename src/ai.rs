@@ -0,0 +1,320 @@
+use crate::asteroid::Asteroid;
+use crate::bodies::*;
+use crate::bullet::{BulletConfig, BulletConfigHandle};
+use crate::console::ConfigExt;
+use crate::control::{AiController, Pawn, PlayerController, ShipPawn};
+use crate::grid::Grid;
+use crate::input_actions::*;
+use crate::schedule::InGameSet;
+use crate::ship::{Pilot, Ship, ShipConfig, ShipConfigHandle, ShipKind};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+// how hard the pilot turns for how far off it is, and how much it eases off when already spinning
+const TURN_GAIN: f32 = 3.0;
+const TURN_DAMPING: f32 = 0.2;
+// turning with nothing to do, so it keeps scanning the field
+const SEARCH_TURN: f32 = 0.3;
+
+#[derive(Deserialize, Clone, Debug)]
+struct Skill {
+    reaction: f32,
+    aim_tolerance: f32,
+    lead: f32,
+    dodge_time: f32,
+    dodge_margin: f32,
+    range: f32,
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct AiConfig {
+    restart_delay: f32,
+    levels: Vec<Skill>,
+}
+
+#[derive(Resource)]
+struct AiConfigHandle {
+    config: Handle<AiConfig>,
+}
+
+// what a pilot decided the last time it looked, held until it looks again
+#[derive(Default)]
+struct Plan {
+    next_look: Duration,
+    inputs: Vec<Input>,
+    fire: bool,
+}
+
+// the pilot's own ship, everything else is measured from it
+struct View {
+    velocity: Vec2,
+    rotation: f32,
+    angular_velocity: f32,
+    radius: f32,
+}
+
+// an asteroid as the pilot sees it, offset the short way round the wrap
+struct Body {
+    offset: Vec2,
+    velocity: Vec2,
+    radius: f32,
+}
+
+type PilotedShip<'a> = (
+    &'a ShipPawn,
+    &'a Position,
+    &'a Velocity,
+    &'a Rotation,
+    &'a AngularVelocity,
+    &'a TimeStamp,
+    &'a RigidBody,
+    &'a ShipKind,
+);
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.ai.ron");
+    commands.insert_resource(AiConfigHandle { config });
+}
+
+fn turn_input(ship: &View, error: f32) -> Input {
+    Input {
+        action: Actions::Rotate,
+        value: (error * TURN_GAIN - ship.angular_velocity * TURN_DAMPING).clamp(-1.0, 1.0),
+    }
+}
+
+// seconds until a bullet fired now meets a body, bullets don't take on the ship's velocity
+fn intercept(offset: Vec2, velocity: Vec2, speed: f32) -> Option<f32> {
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+    if a.abs() < f32::EPSILON {
+        return (b < 0.0).then(|| -c / b);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+        .into_iter()
+        .filter(|time| *time > 0.0)
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+// the soonest asteroid that will pass too close, and which way is clear of it
+fn threat(ship: &View, bodies: &[Body], skill: &Skill) -> Option<Vec2> {
+    bodies
+        .iter()
+        .filter_map(|body| {
+            let relative = body.velocity - ship.velocity;
+            let closest = (-body.offset.dot(relative) / relative.length_squared().max(f32::EPSILON))
+                .clamp(0.0, skill.dodge_time);
+            let miss = body.offset + relative * closest;
+            let clearance = body.radius + ship.radius + skill.dodge_margin;
+            (miss.length() < clearance).then(|| {
+                // head on, step sideways out of its path
+                let away = (-miss).try_normalize().unwrap_or(relative.perp().normalize_or_zero());
+                (closest, away)
+            })
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, away)| away)
+}
+
+fn decide(ship: &View, bodies: &[Body], skill: &Skill, bullet_speed: f32, bullet_range: f32) -> Plan {
+    let mut plan = Plan::default();
+
+    if let Some(away) = threat(ship, bodies, skill) {
+        let error = turn_between(ship.rotation, heading(away));
+        plan.inputs.push(turn_input(ship, error));
+        // thrust only pushes forwards, so wait until it's facing roughly the right way
+        if error.abs() < FRAC_PI_2 {
+            plan.inputs.push(Input {
+                action: Actions::MoveForward,
+                value: 1.0,
+            });
+        }
+        return plan;
+    }
+
+    let target = bodies
+        .iter()
+        .filter(|body| body.offset.length() < skill.range)
+        .filter_map(|body| {
+            let time = intercept(body.offset, body.velocity, bullet_speed)?;
+            (time * bullet_speed < bullet_range + body.radius).then_some((body, time))
+        })
+        .min_by(|a, b| a.0.offset.length_squared().total_cmp(&b.0.offset.length_squared()));
+
+    match target {
+        Some((body, time)) => {
+            let aim = body.offset + body.velocity * time * skill.lead;
+            let error = turn_between(ship.rotation, heading(aim));
+            plan.inputs.push(turn_input(ship, error));
+            plan.fire = error.abs() < skill.aim_tolerance;
+        }
+        None => plan.inputs.push(Input {
+            action: Actions::Rotate,
+            value: SEARCH_TURN,
+        }),
+    }
+    plan
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fly(
+    time: Res<Time>,
+    grid: Option<Res<Grid>>,
+    ai_configs: Res<Assets<AiConfig>>,
+    ai_handle: Res<AiConfigHandle>,
+    bullet_configs: Res<Assets<BulletConfig>>,
+    bullet_handle: Res<BulletConfigHandle>,
+    ship_configs: Res<Assets<ShipConfig>>,
+    ship_handle: Res<ShipConfigHandle>,
+    controllers: Query<(Entity, &AiController)>,
+    ships: Query<PilotedShip, With<Ship>>,
+    asteroids: Query<(&Position, &Velocity, &RigidBody), With<Asteroid>>,
+    mut plans: Local<HashMap<Entity, Plan>>,
+    mut writer: EventWriter<InputEvent>,
+) {
+    let (Some(config), Some(bullet_config), Some(ship_config)) = (
+        ai_configs.get(ai_handle.config.id()),
+        bullet_configs.get(bullet_handle.config.id()),
+        ship_configs.get(ship_handle.config.id()),
+    ) else {
+        return;
+    };
+    let bullet_range = bullet_config.speed * bullet_config.lifetime as f32 / 1000.0;
+
+    plans.retain(|controller, _| controllers.contains(*controller));
+    for (controller, ai) in controllers.iter() {
+        let Some(skill) = config.levels.get(ai.skill).or(config.levels.last()) else {
+            return;
+        };
+        let Some((_, position, velocity, rotation, angular_velocity, last_shot, body, kind)) = ships
+            .iter()
+            .find(|(pawn, ..)| pawn.get_controller() == &controller)
+        else {
+            continue;
+        };
+
+        let plan = plans.entry(controller).or_default();
+        if time.elapsed() >= plan.next_look {
+            let view = View {
+                velocity: velocity.0,
+                rotation: rotation.0,
+                angular_velocity: angular_velocity.0,
+                radius: body.radius,
+            };
+            let bodies: Vec<Body> = asteroids
+                .iter()
+                .map(|(asteroid, asteroid_velocity, asteroid_body)| {
+                    let offset = asteroid.0 - position.0;
+                    Body {
+                        offset: grid.as_ref().map_or(offset, |grid| grid.wrap_delta(offset)),
+                        velocity: asteroid_velocity.0,
+                        radius: asteroid_body.radius,
+                    }
+                })
+                .collect();
+            *plan = decide(&view, &bodies, skill, bullet_config.speed, bullet_range);
+            plan.next_look = time.elapsed() + Duration::from_secs_f32(skill.reaction);
+        }
+
+        for input in plan.inputs.iter() {
+            writer.send(InputEvent {
+                controller,
+                input: *input,
+            });
+        }
+        // only pull the trigger once the gun is ready, so a held plan doesn't waste a shot
+        let fire_delay = ship_config
+            .ship(kind.0)
+            .map_or(Duration::ZERO, |ship| Duration::from_millis(ship.fire_delay));
+        if plan.fire && time.elapsed().saturating_sub(last_shot.0) > fire_delay {
+            writer.send(InputEvent {
+                controller,
+                input: Input {
+                    action: Actions::Shoot,
+                    value: 1.0,
+                },
+            });
+        }
+    }
+}
+
+// keep playing by itself, for attract loops and soak tests
+fn ask_restart(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    configs: Res<Assets<AiConfig>>,
+    config_handle: Res<AiConfigHandle>,
+    controllers: Query<Entity, With<AiController>>,
+    mut since: Local<Option<Duration>>,
+    mut writer: EventWriter<InputEvent>,
+) {
+    if *state.get() != GameState::GameOver {
+        *since = None;
+        return;
+    }
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let since = *since.get_or_insert(time.elapsed());
+    if time.elapsed() - since < Duration::from_secs_f32(config.restart_delay) {
+        return;
+    }
+    if let Some(controller) = controllers.iter().next() {
+        writer.send(InputEvent {
+            controller,
+            input: Input {
+                action: Actions::Restart,
+                value: 1.0,
+            },
+        });
+    }
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<AiConfig>(&["ai.ron"]);
+        app.add_systems(Startup, load_config);
+        app.add_systems(Update, (fly, ask_restart).in_set(InGameSet::GameInput));
+    }
+}
+
+#[derive(Resource)]
+struct Autoplay {
+    skill: usize,
+}
+
+fn spawn_autopilot(mut commands: Commands, autoplay: Res<Autoplay>) {
+    commands.spawn((
+        AiController {
+            id: 0,
+            skill: autoplay.skill,
+        },
+        Pilot::default(),
+    ));
+    // the keyboard keeps pause and the menus, it just has no ship
+    commands.spawn(PlayerController { id: 1 });
+}
+
+// the game plays itself
+pub struct AutoplayPlugin {
+    pub skill: usize,
+}
+
+impl Plugin for AutoplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Autoplay { skill: self.skill });
+        app.add_systems(PreStartup, spawn_autopilot);
+    }
+}
@@ -1,37 +1,61 @@
 use crate::bodies::*;
+use crate::bullet::FiredBy;
+use crate::control::{Pawn, ShipPawn};
+use crate::console::{ConfigExt, ConsoleExt};
+use crate::gravity::Well;
 use crate::grid::*;
 use crate::load_spawner;
+use crate::powerup::Pickup;
+use crate::save::BodyState;
 use crate::schedule::InGameSet;
-use crate::score::Scored;
+use crate::score::{update_score, ScoreSource, Scored};
 use crate::spawner::SpawnGenerator;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy::render::mesh::{self, PrimitiveTopology};
 use bevy::render::render_asset::RenderAssetUsages;
-use bevy::time::common_conditions::on_timer;
-use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_turborand::prelude::*;
 use serde::Deserialize;
-use std::time::Duration;
 //use web_sys::console;
 
 
-#[derive(Deserialize, Asset, Clone, Copy, TypePath)]
-struct AsteroidConfig {
+// the one place asteroid sizes are told apart, scoring, drops and stats all go by these
+#[derive(Deserialize, Clone)]
+pub struct SizeClass {
+    pub name: String,
+    pub min_scale: f32,
+    // smaller rocks are harder to hit so they're worth more
+    pub points: u32,
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+pub struct AsteroidConfig {
     varients: usize,
     num_verts: (usize, usize),
     angle_range: f32,
     radius_range: f32,
     radius_base: f32,
+    // largest first, an asteroid is the first size it's at least as big as
+    pub sizes: Vec<SizeClass>,
+}
+
+impl AsteroidConfig {
+    pub fn size(&self, scale: f32) -> Option<usize> {
+        self.sizes.iter().position(|size| scale >= size.min_scale)
+    }
+
+    fn points(&self, size: Option<usize>) -> u32 {
+        size.and_then(|size| self.sizes.get(size)).map_or(0, |size| size.points)
+    }
 }
 
 #[derive(Resource)]
-struct AsteroidConfigHandle {
-    config: Handle<AsteroidConfig>,
+pub struct AsteroidConfigHandle {
+    pub config: Handle<AsteroidConfig>,
 }
 
 #[derive(Resource)]
-struct AsteroidAssets {
+pub struct AsteroidAssets {
     meshes: Vec<Handle<Mesh>>,
     material: Handle<ColorMaterial>,
 }
@@ -39,6 +63,36 @@ struct AsteroidAssets {
 #[derive(Component)]
 pub struct Asteroid;
 
+// a resource rather than a run condition so saves and rollback can restore it
+#[derive(Resource)]
+pub struct SpawnTimer(pub Timer);
+
+impl Default for SpawnTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(2.0, TimerMode::Repeating))
+    }
+}
+
+// which of the AsteroidAssets meshes this asteroid uses
+#[derive(Component)]
+pub struct MeshVariant(pub usize);
+
+#[derive(Event)]
+pub struct AsteroidDestroyed {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub scale: f32,
+    // index into the config's sizes
+    pub size: Option<usize>,
+}
+
+// clears every asteroid at once, no children, scoring for whoever set it off
+#[derive(Event)]
+pub struct SmartBomb {
+    pub team: u32,
+    pub player: Option<Entity>,
+}
+
 #[derive(Bundle)]
 struct AsteroidBundle {
     asteroid: Asteroid,
@@ -119,12 +173,32 @@ fn spawn_asteroid(
     let mesh = spawner.rng.usize(0..config.varients);
     commands.spawn((
         AsteroidBundle::new(position, velocity, angular_velocity, scale),
+        MeshVariant(mesh),
         Mesh2d(asteroid_assets.meshes[mesh].clone()),
         MeshMaterial2d(asteroid_assets.material.clone()),
         Transform::default(),
     ));
 }
 
+// rebuild a saved asteroid, relinking the mesh by variant index
+pub fn restore_asteroid(world: &mut World, body: &BodyState, variant: usize) -> Option<Entity> {
+    let assets = world.get_resource::<AsteroidAssets>()?;
+    // a mod with no mesh variants has nothing to draw it with
+    let mesh = assets.meshes.get(variant.checked_rem(assets.meshes.len())?)?.clone();
+    let material = assets.material.clone();
+
+    let mut bundle = AsteroidBundle::new(body.position, body.velocity, body.angular_velocity, body.scale);
+    bundle.rotation = Rotation(body.rotation);
+    let entity = world.spawn((
+        bundle,
+        MeshVariant(variant),
+        Mesh2d(mesh),
+        MeshMaterial2d(material),
+        Transform::default(),
+    ));
+    Some(entity.id())
+}
+
 fn spawn_asteroid_child(
     commands: &mut Commands,
     asteroid_assets: &Res<AsteroidAssets>,
@@ -232,12 +306,18 @@ fn create_astroid_mesh(spawner: &mut ResMut<SpawnGenerator>, config: &AsteroidCo
 
 fn spawn_asteroid_random(
     mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<SpawnTimer>,
     asteroid_assets: Res<AsteroidAssets>,
     mut spawner: ResMut<SpawnGenerator>,
     grid: Res<Grid>,
     configs: Res<Assets<AsteroidConfig>>,
     config_handle: Res<AsteroidConfigHandle>,
 ) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
     // spawn position offscreen inside grid extents
     let x_dist = spawner.rng.f32_normalized() * grid.extends;
     let y_dist = spawner.rng.f32_normalized() * grid.extends;
@@ -274,15 +354,19 @@ fn spawn_asteroid_random(
     }
 }
 
+// whatever broke an asteroid, a bullet knows who fired it and a ship who flies it
+type Hitter<'a> = (&'a Collider, Has<Deflecting>, Option<&'a FiredBy>, Option<&'a ShipPawn>);
+
 // TODO! switch spawning children to an event
 fn destroy_asteroids(
     mut commands: Commands,
     asteroid_assets: Option<Res<AsteroidAssets>>,
     mut spawner: ResMut<SpawnGenerator>,
     asteroids: Query<(Entity, &Collider, &Position, &Velocity, &Scale), With<Asteroid>>,
-    colliders: Query<&Collider>,
+    colliders: Query<Hitter>,
     mut collisions: EventReader<Collision>,
     mut score: EventWriter<Scored>,
+    mut destroyed: EventWriter<AsteroidDestroyed>,
     configs: Res<Assets<AsteroidConfig>>,
     config_handle: Res<AsteroidConfigHandle>,
 ) {
@@ -296,10 +380,28 @@ fn destroy_asteroids(
                     if let Ok((ast_entity, ast_collider, ast_pos, ast_vel, ast_scale)) =
                         asteroids.get(entity_a)
                     {
-                        if let Ok(collider) = colliders.get(entity_b) {
-                            if collider.team != ast_collider.team {
-                                // TODO! add teams to score
-                                score.send(Scored);
+                        if let Ok((collider, deflecting, fired_by, pawn)) = colliders.get(entity_b) {
+                            if collider.team != ast_collider.team
+                                && collider.team != PICKUP_TEAM
+                                && !deflecting
+                            {
+                                let size = config.size(ast_scale.0);
+                                score.send(Scored {
+                                    points: config.points(size),
+                                    size,
+                                    position: ast_pos.0,
+                                    source: ScoreSource::Asteroid,
+                                    team: collider.team,
+                                    player: fired_by
+                                        .map(|fired_by| fired_by.0)
+                                        .or(pawn.map(|pawn| *pawn.get_controller())),
+                                });
+                                destroyed.send(AsteroidDestroyed {
+                                    position: ast_pos.0,
+                                    velocity: ast_vel.0,
+                                    scale: ast_scale.0,
+                                    size,
+                                });
                                 if ast_scale.0 > 25.0 {
                                     spawn_asteroid_child(
                                         &mut commands,
@@ -332,6 +434,44 @@ fn destroy_asteroids(
     }
 }
 
+fn detonate_smart_bombs(
+    mut commands: Commands,
+    mut bombs: EventReader<SmartBomb>,
+    asteroids: Query<(Entity, &Position, &Velocity, &Scale), With<Asteroid>>,
+    mut score: EventWriter<Scored>,
+    mut destroyed: EventWriter<AsteroidDestroyed>,
+    configs: Res<Assets<AsteroidConfig>>,
+    config_handle: Res<AsteroidConfigHandle>,
+) {
+    // two bombs in a frame clear no more than one, the first to go off gets the points
+    let Some(bomb) = bombs.read().next() else {
+        return;
+    };
+    let (team, player) = (bomb.team, bomb.player);
+    bombs.clear();
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    for (entity, position, velocity, scale) in asteroids.iter() {
+        let size = config.size(scale.0);
+        score.send(Scored {
+            points: config.points(size),
+            size,
+            position: position.0,
+            source: ScoreSource::SmartBomb,
+            team,
+            player,
+        });
+        destroyed.send(AsteroidDestroyed {
+            position: position.0,
+            velocity: velocity.0,
+            scale: scale.0,
+            size,
+        });
+        commands.entity(entity).despawn();
+    }
+}
+
 fn bounce_asteroids(
     mut asteroids: Query<(&mut Position, &mut Velocity, &RigidBody), With<Asteroid>>,
     mut collisions: EventReader<Collision>,
@@ -370,6 +510,63 @@ fn bounce_asteroids(
     }
 }
 
+fn spawn_command(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    asteroid_assets: Option<Res<AsteroidAssets>>,
+    mut spawner: ResMut<SpawnGenerator>,
+    configs: Res<Assets<AsteroidConfig>>,
+    config_handle: Res<AsteroidConfigHandle>,
+) -> String {
+    let numbers: Vec<f32> = args.iter().skip(1).filter_map(|arg| arg.parse().ok()).collect();
+    let (Some("asteroid"), [x, y, scale]) = (args.first().map(String::as_str), numbers.as_slice())
+    else {
+        return "spawn asteroid <x> <y> <scale>".to_string();
+    };
+    let (Some(assets), Some(config)) = (asteroid_assets, configs.get(config_handle.config.id())) else {
+        return "asteroids haven't loaded yet".to_string();
+    };
+    spawn_asteroid(
+        &mut commands,
+        &assets,
+        &mut spawner,
+        config,
+        Vec2::new(*x, *y),
+        Vec2::ZERO,
+        0.0,
+        *scale,
+    );
+    format!("spawned an asteroid at {} {}", x, y)
+}
+
+fn seed_command(In(args): In<Vec<String>>, mut spawner: ResMut<SpawnGenerator>) -> String {
+    match args.first().and_then(|seed| seed.parse::<u64>().ok()) {
+        Some(seed) => {
+            spawner.rng = RngComponent::with_seed(seed);
+            format!("asteroids now spawn from seed {}", seed)
+        }
+        None => "seed <n>".to_string(),
+    }
+}
+
+// everything drifting about that isn't a ship or its bullets
+type Killable = Or<(With<Asteroid>, With<Pickup>, With<Well>)>;
+
+fn kill_command(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    bodies: Query<Entity, Killable>,
+) -> String {
+    if args.first().map(String::as_str) != Some("all") {
+        return "kill all".to_string();
+    }
+    let count = bodies.iter().count();
+    for entity in bodies.iter() {
+        commands.entity(entity).despawn();
+    }
+    format!("removed {} asteroids, pickups and wells", count)
+}
+
 fn despawn_asteroids(mut commands: Commands, asteroids: Query<Entity, With<Asteroid>>) {
     for entity in asteroids.iter() {
         commands.entity(entity).despawn();
@@ -380,21 +577,26 @@ pub struct AsteroidsPlugin;
 
 impl Plugin for AsteroidsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RonAssetPlugin::<AsteroidConfig>::new(&["ast.ron"]));
+        app.add_config::<AsteroidConfig>(&["ast.ron"]);
+        app.add_event::<AsteroidDestroyed>();
+        app.add_event::<SmartBomb>();
+        app.init_resource::<SpawnTimer>();
         app.add_systems(Startup, (load_spawner, load_config));
         app.add_systems(Update, (load_asteroids).in_set(InGameSet::LoadEntities));
         app.add_systems(
             Update,
-            (destroy_asteroids).in_set(InGameSet::DespawnEntities),
+            (destroy_asteroids, detonate_smart_bombs.after(destroy_asteroids))
+                .before(update_score)
+                .in_set(InGameSet::DespawnEntities),
         );
         app.add_systems(
             Update,
-            (
-                spawn_asteroid_random.run_if(on_timer(Duration::from_secs(2))),
-            )
-                .in_set(InGameSet::UpdateEntities),
+            (spawn_asteroid_random).in_set(InGameSet::UpdateEntities),
         );
         app.add_systems(Update, (bounce_asteroids).in_set(InGameSet::CollisionReaction));
         app.add_systems(OnEnter(GameState::GameOver), despawn_asteroids);
+        app.add_console_command("spawn", "spawn asteroid <x> <y> <scale>", spawn_command);
+        app.add_console_command("seed", "seed <n>, reseeds asteroid spawning", seed_command);
+        app.add_console_command("kill", "kill all, removes every asteroid, pickup and well", kill_command);
     }
 }
@@ -0,0 +1,499 @@
+use crate::asteroid::{Asteroid, AsteroidDestroyed};
+use crate::bodies::Acceleration;
+use crate::bullet::CreateBullet;
+use crate::console::ConfigExt;
+use crate::schedule::{resimulating, FreshEvents, InGameSet};
+use crate::ship::{Ship, ShipDestroyed};
+use crate::GameState;
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+    Noise,
+}
+
+// all times are in seconds, frequencies in hz
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct SoundDef {
+    pub wave: Waveform,
+    // linear sweep from the first to the second frequency over the duration
+    pub frequency: (f32, f32),
+    pub duration: f32,
+    pub attack: f32,
+    pub release: f32,
+    pub volume: f32,
+    // fraction of the period the square wave is high
+    pub duty: f32,
+    // rate and depth of frequency wobble
+    pub vibrato: (f32, f32),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct SoundBank {
+    shoot: SoundDef,
+    bang: SoundDef,
+    thrust: SoundDef,
+    death: SoundDef,
+    beat_low: SoundDef,
+    beat_high: SoundDef,
+}
+
+#[derive(Deserialize, Asset, Clone, Copy, TypePath)]
+struct AudioConfig {
+    sample_rate: u32,
+    master: f32,
+    sfx: f32,
+    music: f32,
+    // asteroids of this scale break at the base pitch, smaller ones play higher
+    bang_scale: f32,
+    // heartbeat interval in ms with a full field and with one asteroid left
+    heartbeat: (u64, u64),
+    sounds: SoundBank,
+}
+
+#[derive(Resource)]
+struct AudioConfigHandle {
+    config: Handle<AudioConfig>,
+}
+
+// runtime volume settings, seeded from the config
+#[derive(Resource, Clone, Copy)]
+pub struct AudioVolume {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+}
+
+impl AudioVolume {
+    fn sfx(&self) -> Volume {
+        Volume::new(self.master * self.sfx)
+    }
+
+    fn music(&self) -> Volume {
+        Volume::new(self.master * self.music)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Shoot,
+    Bang,
+    Death,
+    // the heartbeat, played at the music volume
+    BeatLow,
+    BeatHigh,
+}
+
+impl Sound {
+    fn is_music(self) -> bool {
+        matches!(self, Sound::BeatLow | Sound::BeatHigh)
+    }
+}
+
+// lets other systems trigger one shot sounds, speed also shifts the pitch
+#[derive(Event)]
+pub struct PlaySound {
+    pub sound: Sound,
+    pub speed: f32,
+}
+
+#[derive(Resource)]
+struct SoundAssets {
+    shoot: Handle<AudioSource>,
+    bang: Handle<AudioSource>,
+    thrust: Handle<AudioSource>,
+    death: Handle<AudioSource>,
+    beat_low: Handle<AudioSource>,
+    beat_high: Handle<AudioSource>,
+}
+
+impl SoundAssets {
+    fn get(&self, sound: Sound) -> Handle<AudioSource> {
+        match sound {
+            Sound::Shoot => self.shoot.clone(),
+            Sound::Bang => self.bang.clone(),
+            Sound::Death => self.death.clone(),
+            Sound::BeatLow => self.beat_low.clone(),
+            Sound::BeatHigh => self.beat_high.clone(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ThrustSound;
+
+#[derive(Resource, Default)]
+struct Heartbeat {
+    timer: Timer,
+    high: bool,
+    peak: usize,
+}
+
+// small deterministic generator so noise sounds identical on every build
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (self.0 >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+    }
+}
+
+fn oscillator(wave: Waveform, phase: f32, duty: f32, held: f32) -> f32 {
+    match wave {
+        Waveform::Square => {
+            if phase < duty {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+        Waveform::Sawtooth => 2.0 * phase - 1.0,
+        Waveform::Sine => (phase * TAU).sin(),
+        // sample and hold, a new random level every period
+        Waveform::Noise => held,
+    }
+}
+
+fn envelope(sound: &SoundDef, t: f32) -> f32 {
+    let attack = if sound.attack > 0.0 {
+        (t / sound.attack).min(1.0)
+    } else {
+        1.0
+    };
+    let release = if sound.release > 0.0 {
+        ((sound.duration - t) / sound.release).min(1.0)
+    } else {
+        1.0
+    };
+    attack * release.max(0.0)
+}
+
+// generate mono samples in the range -1..1, pure so it can be checked without an audio device
+pub fn synthesize(sound: &SoundDef, sample_rate: u32) -> Vec<f32> {
+    let count = (sound.duration * sample_rate as f32) as usize;
+    let mut samples = Vec::with_capacity(count);
+    let mut noise = Lcg(0x2545_f491);
+    let mut held = noise.next();
+    let mut phase = 0.0;
+
+    for i in 0..count {
+        let t = i as f32 / sample_rate as f32;
+        let progress = t / sound.duration;
+        let sweep = sound.frequency.0 + (sound.frequency.1 - sound.frequency.0) * progress;
+        let frequency = sweep + (t * sound.vibrato.0 * TAU).sin() * sound.vibrato.1;
+
+        samples.push(oscillator(sound.wave, phase, sound.duty, held) * envelope(sound, t) * sound.volume);
+
+        phase += frequency.max(0.0) / sample_rate as f32;
+        if phase >= 1.0 {
+            phase -= phase.floor();
+            held = noise.next();
+        }
+    }
+    samples
+}
+
+// 16 bit mono pcm, which bevy can decode with the wav feature
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    // pcm, one channel
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.audio.ron");
+    commands.insert_resource(AudioConfigHandle { config });
+}
+
+fn load_sounds(
+    mut commands: Commands,
+    mut sources: ResMut<Assets<AudioSource>>,
+    configs: Res<Assets<AudioConfig>>,
+    config_handle: Res<AudioConfigHandle>,
+    sound_assets: Option<Res<SoundAssets>>,
+) {
+    if sound_assets.is_some() {
+        return;
+    }
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        let mut build = |sound: &SoundDef| {
+            let samples = synthesize(sound, config.sample_rate);
+            sources.add(AudioSource {
+                bytes: encode_wav(&samples, config.sample_rate).into(),
+            })
+        };
+        let assets = SoundAssets {
+            shoot: build(&config.sounds.shoot),
+            bang: build(&config.sounds.bang),
+            thrust: build(&config.sounds.thrust),
+            death: build(&config.sounds.death),
+            beat_low: build(&config.sounds.beat_low),
+            beat_high: build(&config.sounds.beat_high),
+        };
+        let volume = AudioVolume {
+            master: config.master,
+            sfx: config.sfx,
+            music: config.music,
+        };
+
+        commands.spawn((
+            ThrustSound,
+            AudioPlayer::new(assets.thrust.clone()),
+            PlaybackSettings::LOOP.with_volume(volume.sfx()).paused(),
+        ));
+        commands.insert_resource(volume);
+        commands.insert_resource(assets);
+    }
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    sound_assets: Option<Res<SoundAssets>>,
+    volume: Option<Res<AudioVolume>>,
+    mut events: EventReader<PlaySound>,
+) {
+    if let (Some(assets), Some(volume)) = (sound_assets, volume) {
+        for event in events.read() {
+            let level = if event.sound.is_music() {
+                volume.music()
+            } else {
+                volume.sfx()
+            };
+            commands.spawn((
+                AudioPlayer::new(assets.get(event.sound)),
+                PlaybackSettings::DESPAWN
+                    .with_volume(level)
+                    .with_speed(event.speed),
+            ));
+        }
+    }
+}
+
+fn shot_sounds(mut events: FreshEvents<CreateBullet>, mut sounds: EventWriter<PlaySound>) {
+    for _ in events.read() {
+        sounds.send(PlaySound {
+            sound: Sound::Shoot,
+            speed: 1.0,
+        });
+    }
+}
+
+fn break_sounds(
+    configs: Res<Assets<AudioConfig>>,
+    config_handle: Res<AudioConfigHandle>,
+    mut events: FreshEvents<AsteroidDestroyed>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        for event in events.read() {
+            sounds.send(PlaySound {
+                sound: Sound::Bang,
+                speed: (config.bang_scale / event.scale).clamp(0.5, 4.0),
+            });
+        }
+    }
+}
+
+fn death_sounds(mut events: FreshEvents<ShipDestroyed>, mut sounds: EventWriter<PlaySound>) {
+    for _ in events.read() {
+        sounds.send(PlaySound {
+            sound: Sound::Death,
+            speed: 1.0,
+        });
+    }
+}
+
+// keep the thrust loop running while any ship accelerates forward
+fn thrust_sound(
+    state: Res<State<GameState>>,
+    ships: Query<&Acceleration, With<Ship>>,
+    sinks: Query<&AudioSink, With<ThrustSound>>,
+) {
+    let thrusting = *state.get() == GameState::InGame
+        && ships.iter().any(|acceleration| acceleration.0.y > 0.0);
+    for sink in sinks.iter() {
+        if thrusting && sink.is_paused() {
+            sink.play();
+        } else if !thrusting && !sink.is_paused() {
+            sink.pause();
+        }
+    }
+}
+
+// beats get closer together as the field empties, like the arcade
+fn heartbeat(
+    time: Res<Time>,
+    mut heartbeat: ResMut<Heartbeat>,
+    configs: Res<Assets<AudioConfig>>,
+    config_handle: Res<AudioConfigHandle>,
+    asteroids: Query<(), With<Asteroid>>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+
+    let remaining = asteroids.iter().count();
+    if remaining == 0 {
+        heartbeat.peak = 0;
+        return;
+    }
+    heartbeat.peak = heartbeat.peak.max(remaining);
+
+    heartbeat.timer.tick(time.delta());
+    if heartbeat.timer.finished() {
+        let fraction = remaining as f32 / heartbeat.peak as f32;
+        let (slow, fast) = config.heartbeat;
+        let interval = fast as f32 + (slow as f32 - fast as f32) * fraction;
+        heartbeat.timer = Timer::new(Duration::from_millis(interval as u64), TimerMode::Once);
+
+        let sound = if heartbeat.high {
+            Sound::BeatHigh
+        } else {
+            Sound::BeatLow
+        };
+        heartbeat.high = !heartbeat.high;
+        sounds.send(PlaySound { sound, speed: 1.0 });
+    }
+}
+
+fn reset_heartbeat(mut heartbeat: ResMut<Heartbeat>) {
+    *heartbeat = Heartbeat::default();
+}
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<AudioConfig>(&["audio.ron"]);
+        app.add_event::<PlaySound>();
+        app.init_resource::<Heartbeat>();
+        app.add_systems(Startup, load_config);
+        app.add_systems(Update, (load_sounds).in_set(InGameSet::LoadEntities));
+        app.add_systems(
+            Update,
+            (shot_sounds, break_sounds, death_sounds, heartbeat.run_if(not(resimulating)))
+                .in_set(InGameSet::CollisionReaction),
+        );
+        app.add_systems(Update, (play_sounds, thrust_sound).after(InGameSet::RenderSetup));
+        app.add_systems(OnEnter(GameState::GameOver), reset_heartbeat);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(wave: Waveform) -> SoundDef {
+        SoundDef {
+            wave,
+            frequency: (440.0, 220.0),
+            duration: 0.5,
+            attack: 0.01,
+            release: 0.1,
+            volume: 0.8,
+            duty: 0.5,
+            vibrato: (0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn sample_count_follows_duration() {
+        let samples = synthesize(&tone(Waveform::Square), 22_050);
+        assert_eq!(samples.len(), 11_025);
+        let empty = SoundDef {
+            duration: 0.0,
+            ..tone(Waveform::Sine)
+        };
+        assert!(synthesize(&empty, 22_050).is_empty());
+    }
+
+    #[test]
+    fn silent_at_zero_volume() {
+        for wave in [
+            Waveform::Square,
+            Waveform::Triangle,
+            Waveform::Sawtooth,
+            Waveform::Sine,
+            Waveform::Noise,
+        ] {
+            let silent = SoundDef {
+                volume: 0.0,
+                ..tone(wave)
+            };
+            let samples = synthesize(&silent, 8_000);
+            assert!(samples.iter().all(|sample| *sample == 0.0), "{:?}", wave);
+        }
+    }
+
+    #[test]
+    fn envelope_starts_and_ends_quiet() {
+        let sound = tone(Waveform::Square);
+        let sample_rate = 8_000;
+        let samples = synthesize(&sound, sample_rate);
+        assert_eq!(samples[0], 0.0);
+        // the last sample is a single step away from the end of the release
+        let last = samples[samples.len() - 1].abs();
+        assert!(last <= sound.volume / (sound.release * sample_rate as f32) + 1e-6, "{}", last);
+        // full volume in between
+        let middle = samples[samples.len() / 2].abs();
+        assert!((middle - sound.volume).abs() < 1e-6, "{}", middle);
+        assert!(samples.iter().all(|sample| sample.abs() <= sound.volume + 1e-6));
+    }
+
+    #[test]
+    fn wav_header_fields() {
+        let samples = [0.0, 1.0, -1.0, 2.0];
+        let bytes = encode_wav(&samples, 11_025);
+        let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        assert_eq!(bytes.len(), 44 + samples.len() * 2);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(16), 16);
+        // pcm, mono
+        assert_eq!(u16_at(20), 1);
+        assert_eq!(u16_at(22), 1);
+        assert_eq!(u32_at(24), 11_025);
+        // byte rate and block align for 16 bit mono
+        assert_eq!(u32_at(28), 22_050);
+        assert_eq!(u16_at(32), 2);
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(40), 8);
+        // out of range samples are clamped
+        assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), i16::MAX);
+        assert_eq!(i16::from_le_bytes([bytes[48], bytes[49]]), -i16::MAX);
+        assert_eq!(i16::from_le_bytes([bytes[50], bytes[51]]), i16::MAX);
+    }
+}
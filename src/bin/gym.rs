@@ -0,0 +1,48 @@
+// json lines over stdin and stdout for training bots, logs go to stderr
+//   gym --nearest 8 --frame-skip 4 --death-penalty 10
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use asteroids::{parse_or, serve, Env, EnvConfig};
+
+    let mut env = match Env::new(EnvConfig::default()) {
+        Ok(env) => env,
+        Err(e) => {
+            eprintln!("could not start the game: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // read once the game's logging is up, so a bad value gets reported
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+    };
+    let defaults = EnvConfig::default();
+    env.set_config(EnvConfig {
+        nearest: parse_or(
+            value("--nearest"),
+            "--nearest takes an asteroid count",
+            defaults.nearest,
+        ),
+        frame_skip: parse_or(
+            value("--frame-skip"),
+            "--frame-skip takes a frame count",
+            defaults.frame_skip,
+        ),
+        death_penalty: parse_or(
+            value("--death-penalty"),
+            "--death-penalty takes a number",
+            defaults.death_penalty,
+        ),
+    });
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    if let Err(e) = serve(&mut env, stdin.lock(), stdout.lock()) {
+        eprintln!("gym stopped: {}", e);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,39 @@
+// headless authoritative server, players and spectators join with --connect
+//   server --bind 0.0.0.0:7100 --players 2
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use asteroids::{
+        headless_plugins, parse_or, AsteroidsGamePlugin, ServerPlugin, ServerSettings, TICK_RATE,
+    };
+    use bevy::app::ScheduleRunnerPlugin;
+    use bevy::prelude::*;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    let mut app = App::new();
+    app.add_plugins(headless_plugins())
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f32(1.0 / TICK_RATE)));
+
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+    };
+    let settings = ServerSettings {
+        bind: parse_or(
+            value("--bind"),
+            "--bind takes an address like 0.0.0.0:7100",
+            SocketAddr::from(([0, 0, 0, 0], 7100)),
+        ),
+        players: parse_or(value("--players"), "--players takes a count", 2),
+        send_interval: parse_or(value("--send-interval"), "--send-interval takes a frame count", 2),
+    };
+
+    app.add_plugins(AsteroidsGamePlugin)
+        .add_plugins(ServerPlugin { settings })
+        .run();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
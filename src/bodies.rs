@@ -1,5 +1,6 @@
 use crate::schedule::InGameSet;
 use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
 use std::time::Duration;
 //use web_sys::console;
 
@@ -11,6 +12,16 @@ pub struct TimeStamp(pub Duration);
 #[derive(Component)]
 pub struct Rotation(pub f32);
 
+// the Rotation a ship has facing a direction, ships point along +y at zero
+pub fn heading(direction: Vec2) -> f32 {
+    (-direction.x).atan2(direction.y)
+}
+
+// the short way round from one rotation to another, between -PI and PI
+pub fn turn_between(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
 #[derive(Component)]
 pub struct AngularVelocity(pub f32);
 
@@ -44,6 +55,10 @@ pub struct NeedsConfig;
 #[derive(Component)]
 pub struct NeedsMaterial;
 
+// whatever hits this bounces off, neither side is hurt
+#[derive(Component)]
+pub struct Deflecting;
+
 #[derive(Component)]
 pub struct RigidBody {
     pub radius: f32,
@@ -92,6 +107,9 @@ pub struct Collision {
 }
 
 // lets call asteroids team 0
+// pickups are on their own team, only ships do anything when they touch one
+pub const PICKUP_TEAM: u32 = u32::MAX;
+
 #[derive(Component, Debug)]
 pub struct Collider {
     pub team: u32,
@@ -127,7 +145,7 @@ fn update_rotation(time: Res<Time>, mut obj: Query<(&mut Rotation, &AngularVeloc
         rotation.0 += angular_velocity.0 * time.delta_secs();
     }
 }
-fn update_velocity(time: Res<Time>, mut obj: Query<(&mut Velocity, &Acceleration, &Rotation)>) {
+pub fn update_velocity(time: Res<Time>, mut obj: Query<(&mut Velocity, &Acceleration, &Rotation)>) {
     for (mut velocity, acceleration, rotation) in obj.iter_mut() {
         let rotator = Rot2::radians(rotation.0);
         velocity.0 += rotator * acceleration.0 * time.delta_secs();
@@ -1,30 +1,33 @@
-use crate::{bodies::*, schedule::InGameSet, GameState};
+use crate::{bodies::*, console::{ConfigExt, ConsoleExt}, save::BodyState, schedule::InGameSet, GameState};
 use bevy::prelude::*;
-use bevy_common_assets::ron::RonAssetPlugin;
 use serde::Deserialize;
 use std::time::Duration;
 
 #[derive(Deserialize, Asset, Clone, Copy, TypePath)]
-struct BulletConfig {
-    speed: f32,
-    lifetime: u64,
+pub struct BulletConfig {
+    pub speed: f32,
+    pub lifetime: u64,
     size: f32,
     color: (f32, f32, f32),
 }
 
 #[derive(Resource)]
-struct BulletConfigHandle {
-    config: Handle<BulletConfig>,
+pub struct BulletConfigHandle {
+    pub config: Handle<BulletConfig>,
 }
 
 #[derive(Resource)]
-struct BulletAssets {
+pub struct BulletAssets {
     mesh: Handle<Mesh>,
     material: Handle<ColorMaterial>,
 }
 
 #[derive(Component)]
-struct Bullet;
+pub struct Bullet;
+
+// the controller whose ship fired it, for the scores
+#[derive(Component)]
+pub struct FiredBy(pub Entity);
 
 #[derive(Bundle)]
 struct BulletBundle {
@@ -40,7 +43,7 @@ struct BulletBundle {
 }
 
 impl BulletBundle {
-    fn new(position: Vec2, rotation: f32, spawn_time: Duration, speed: f32) -> Self {
+    fn new(position: Vec2, rotation: f32, spawn_time: Duration, speed: f32, team: u32) -> Self {
         Self {
             bullet: Bullet,
             position: Position(position),
@@ -53,8 +56,7 @@ impl BulletBundle {
                 radius: 0.02,
                 mass: 2.0,
             },
-            // TODO! inherit team from ship
-            collider: Collider { team: 1 },
+            collider: Collider { team },
         }
     }
 }
@@ -95,6 +97,9 @@ fn load_bullet(
 pub struct CreateBullet {
     pub position: Vec2,
     pub rotation: f32,
+    // bullets belong to the team of the ship that fired them
+    pub team: u32,
+    pub shooter: Entity,
 }
 
 // TODO! switch to spawning bullets with an event
@@ -111,7 +116,14 @@ fn spawn_bullet(
         if let Some(assets) = bullet_assets {
             for event in events.read() {
                 commands.spawn((
-                    BulletBundle::new(event.position, event.rotation, time.elapsed(), config.speed),
+                    BulletBundle::new(
+                        event.position,
+                        event.rotation,
+                        time.elapsed(),
+                        config.speed,
+                        event.team,
+                    ),
+                    FiredBy(event.shooter),
                     Mesh2d(assets.mesh.clone()),
                     MeshMaterial2d(assets.material.clone()),
                     Transform::default(),
@@ -121,6 +133,32 @@ fn spawn_bullet(
     }
 }
 
+// rebuild a saved bullet, spawn time keeps its remaining lifetime
+pub fn restore_bullet(
+    world: &mut World,
+    body: &BodyState,
+    team: u32,
+    spawn_time: Duration,
+    shooter: Option<Entity>,
+) -> Option<Entity> {
+    let assets = world.get_resource::<BulletAssets>()?;
+    let mesh = assets.mesh.clone();
+    let material = assets.material.clone();
+
+    let mut bundle = BulletBundle::new(body.position, body.rotation, spawn_time, 0.0, team);
+    bundle.velocity = Velocity(body.velocity);
+    let mut entity = world.spawn((
+        bundle,
+        Mesh2d(mesh),
+        MeshMaterial2d(material),
+        Transform::default(),
+    ));
+    if let Some(shooter) = shooter {
+        entity.insert(FiredBy(shooter));
+    }
+    Some(entity.id())
+}
+
 fn destroy_bullets(
     mut commands: Commands,
     bullets: Query<(Entity, &TimeStamp), With<Bullet>>,
@@ -147,13 +185,13 @@ fn collisions_bullets(
     for event in collisions.read() {
         if let Ok((ship, ship_collider)) = bullets.get(event.entity1) {
             if let Ok((_, collider)) = colliders.get(event.entity2) {
-                if collider.team != ship_collider.team {
+                if collider.team != ship_collider.team && collider.team != PICKUP_TEAM {
                     commands.entity(ship).despawn();
                 }
             }
         } else if let Ok((ship, ship_collider)) = bullets.get(event.entity2) {
             if let Ok((_, collider)) = colliders.get(event.entity1) {
-                if collider.team != ship_collider.team {
+                if collider.team != ship_collider.team && collider.team != PICKUP_TEAM {
                     commands.entity(ship).despawn();
                 }
             }
@@ -169,12 +207,29 @@ fn despawn_bullets(mut commands: Commands, bullets: Query<Entity, With<Bullet>>)
     }
 }
 
+fn speed_command(
+    In(args): In<Vec<String>>,
+    mut configs: ResMut<Assets<BulletConfig>>,
+    config_handle: Res<BulletConfigHandle>,
+) -> String {
+    let Some(config) = configs.get_mut(config_handle.config.id()) else {
+        return "bullets haven't loaded yet".to_string();
+    };
+    match args.first().and_then(|speed| speed.parse::<f32>().ok()) {
+        Some(speed) => {
+            config.speed = speed;
+            format!("bullets fly at {}", speed)
+        }
+        None => format!("bullets fly at {}, bulletspeed <speed>", config.speed),
+    }
+}
+
 pub struct BulletPlugin;
 
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<CreateBullet>();
-        app.add_plugins(RonAssetPlugin::<BulletConfig>::new(&["bullet.ron"]));
+        app.add_config::<BulletConfig>(&["bullet.ron"]);
         app.add_systems(Startup, load_config);
         app.add_systems(Update, (load_bullet).in_set(InGameSet::LoadEntities));
         app.add_systems(
@@ -183,5 +238,6 @@ impl Plugin for BulletPlugin {
         );
         app.add_systems(Update, (spawn_bullet).in_set(InGameSet::CollisionReaction));
         app.add_systems(OnEnter(GameState::GameOver), despawn_bullets);
+        app.add_console_command("bulletspeed", "bulletspeed <speed>, until configs reload", speed_command);
     }
 }
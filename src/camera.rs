@@ -0,0 +1,208 @@
+use crate::asteroid::Asteroid;
+use crate::bodies::*;
+use crate::console::ConfigExt;
+use crate::control::{Controller, Pawn, PlayerController, ShipPawn};
+use crate::grid::{CameraFocus, Grid, Playfield};
+use crate::schedule::InGameSet;
+use crate::ship::Ship;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+#[derive(Deserialize, Asset, Clone, Copy, TypePath)]
+struct CameraConfig {
+    // how quickly the camera catches up, higher is snappier
+    smoothing: f32,
+    // seconds of ship velocity to look ahead by
+    look_ahead: f32,
+    minimap_width: f32,
+    minimap_margin: f32,
+    minimap_background: (f32, f32, f32, f32),
+    asteroid_color: (f32, f32, f32),
+    ship_color: (f32, f32, f32),
+}
+
+#[derive(Resource)]
+struct CameraConfigHandle {
+    config: Handle<CameraConfig>,
+}
+
+#[derive(Component)]
+struct Minimap;
+
+#[derive(Component)]
+struct MinimapDot {
+    target: Entity,
+}
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.camera.ron");
+    commands.insert_resource(CameraConfigHandle { config });
+}
+
+fn scrolling(playfield: Option<Res<Playfield>>) -> bool {
+    matches!(playfield.as_deref(), Some(Playfield::Scrolling { .. }))
+}
+
+// the lowest numbered local player's ship, any ship when nobody here is flying, like in autoplay
+fn followed<'a>(
+    ships: &'a Query<(&Position, &Velocity, &ShipPawn), With<Ship>>,
+    players: &Query<(Entity, &PlayerController)>,
+) -> Option<(&'a Position, &'a Velocity)> {
+    let player = players
+        .iter()
+        .min_by_key(|(_, controller)| *controller.get_id())
+        .map(|(entity, _)| entity);
+    ships
+        .iter()
+        .find(|(_, _, pawn)| Some(*pawn.get_controller()) == player)
+        .or_else(|| ships.iter().next())
+        .map(|(position, velocity, _)| (position, velocity))
+}
+
+fn follow_ship(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    mut focus: ResMut<CameraFocus>,
+    configs: Res<Assets<CameraConfig>>,
+    config_handle: Res<CameraConfigHandle>,
+    ships: Query<(&Position, &Velocity, &ShipPawn), With<Ship>>,
+    players: Query<(Entity, &PlayerController)>,
+) {
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        if let Some((position, velocity)) = followed(&ships, &players) {
+            let target = position.0 + velocity.0 * config.look_ahead;
+            // always chase the short way round the torus
+            let offset = grid.wrap_delta(target - focus.0);
+            let blend = 1.0 - (-config.smoothing * time.delta_secs()).exp();
+            let moved = focus.0 + offset * blend;
+            focus.0 = grid.wrap_delta(moved);
+        }
+    }
+}
+
+fn spawn_minimap(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    configs: Res<Assets<CameraConfig>>,
+    config_handle: Res<CameraConfigHandle>,
+    minimaps: Query<(), With<Minimap>>,
+) {
+    if !minimaps.is_empty() {
+        return;
+    }
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        let wrap_size = grid.wrap_size();
+        let height = config.minimap_width * wrap_size.y / wrap_size.x;
+        let background = config.minimap_background;
+        commands.spawn((
+            Minimap,
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(config.minimap_margin),
+                bottom: Val::Px(config.minimap_margin),
+                width: Val::Px(config.minimap_width),
+                height: Val::Px(height),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(background.0, background.1, background.2, background.3)),
+        ));
+    }
+}
+
+type OnMinimap = Or<(With<Asteroid>, With<Ship>)>;
+
+fn update_minimap(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    configs: Res<Assets<CameraConfig>>,
+    config_handle: Res<CameraConfigHandle>,
+    minimaps: Query<Entity, With<Minimap>>,
+    tracked: Query<(Entity, &Position, Has<Ship>), OnMinimap>,
+    mut dots: Query<(Entity, &MinimapDot, &mut Node)>,
+) {
+    let Ok(minimap) = minimaps.get_single() else {
+        return;
+    };
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+
+    let wrap_size = grid.wrap_size();
+    let to_percent = |position: Vec2| {
+        let normalized = (position + wrap_size * 0.5) / wrap_size;
+        (Val::Percent(normalized.x * 100.0), Val::Percent((1.0 - normalized.y) * 100.0))
+    };
+
+    let mut seen = Vec::new();
+    for (entity, dot, mut node) in dots.iter_mut() {
+        if let Ok((_, position, _)) = tracked.get(dot.target) {
+            (node.left, node.top) = to_percent(position.0);
+            seen.push(dot.target);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for (entity, position, is_ship) in tracked.iter() {
+        if seen.contains(&entity) {
+            continue;
+        }
+        let (color, size) = if is_ship {
+            (config.ship_color, 5.0)
+        } else {
+            (config.asteroid_color, 3.0)
+        };
+        let (left, top) = to_percent(position.0);
+        let dot = commands
+            .spawn((
+                MinimapDot { target: entity },
+                Node {
+                    position_type: PositionType::Absolute,
+                    left,
+                    top,
+                    width: Val::Px(size),
+                    height: Val::Px(size),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(color.0, color.1, color.2)),
+            ))
+            .id();
+        commands.entity(minimap).add_child(dot);
+    }
+}
+
+fn reset_focus(mut focus: ResMut<CameraFocus>) {
+    *focus = CameraFocus::default();
+}
+
+fn despawn_dots(mut commands: Commands, dots: Query<Entity, With<MinimapDot>>) {
+    for entity in dots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<CameraConfig>(&["camera.ron"]);
+        app.add_systems(Startup, load_config);
+        app.add_systems(
+            Update,
+            (follow_ship)
+                .run_if(scrolling)
+                .in_set(InGameSet::CollisionReaction),
+        );
+        app.add_systems(
+            Update,
+            (spawn_minimap, update_minimap)
+                .chain()
+                .run_if(scrolling)
+                .in_set(InGameSet::RenderSetup),
+        );
+        app.add_systems(OnEnter(GameState::GameOver), despawn_dots);
+        // the next run starts in the middle wherever the last one ended
+        app.add_systems(OnExit(GameState::GameOver), reset_focus);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::asteroid::restore_asteroid;
+use crate::bullet::restore_bullet;
+use crate::control::PlayerController;
+use crate::bodies::*;
+use crate::grid::Grid;
+use crate::input_actions::*;
+use crate::replication::*;
+use crate::save::BodyState;
+use crate::schedule::InGameSet;
+use crate::score::Score;
+use crate::ship::restore_ship;
+use crate::states::StatesLocked;
+use crate::GameState;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+// how far behind the newest snapshot we draw, in server ticks, so there's always one to blend towards
+const INTERPOLATION_DELAY: f32 = 6.0;
+
+#[derive(Resource, Clone, Debug)]
+pub struct ClientSettings {
+    // a websocket url, e.g. ws://127.0.0.1:7100
+    pub server: String,
+    pub spectate: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod transport {
+    use std::net::TcpStream;
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::{Message, WebSocket};
+
+    pub struct Connection {
+        socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    }
+
+    impl Connection {
+        pub fn open(url: &str) -> Result<Self, String> {
+            let (socket, _) = tungstenite::connect(url).map_err(|e| e.to_string())?;
+            if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+                stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+            }
+            Ok(Self { socket })
+        }
+
+        pub fn send(&mut self, text: String) {
+            // a full buffer keeps the message queued, the next send flushes it
+            let _ = self.socket.send(Message::Text(text));
+        }
+
+        pub fn receive(&mut self) -> Vec<String> {
+            let mut messages = Vec::new();
+            while let Ok(message) = self.socket.read() {
+                if let Message::Text(text) = message {
+                    messages.push(text);
+                }
+            }
+            messages
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod transport {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{MessageEvent, WebSocket};
+
+    pub struct Connection {
+        socket: WebSocket,
+        inbox: Rc<RefCell<VecDeque<String>>>,
+        // the browser opens the socket in the background, hold anything sent before then
+        outbox: Vec<String>,
+        _on_message: Closure<dyn FnMut(MessageEvent)>,
+    }
+
+    impl Connection {
+        pub fn open(url: &str) -> Result<Self, String> {
+            let socket = WebSocket::new(url).map_err(|e| format!("{:?}", e))?;
+            let inbox = Rc::new(RefCell::new(VecDeque::new()));
+            let queue = inbox.clone();
+            let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                if let Some(text) = event.data().as_string() {
+                    queue.borrow_mut().push_back(text);
+                }
+            });
+            socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            Ok(Self {
+                socket,
+                inbox,
+                outbox: Vec::new(),
+                _on_message: on_message,
+            })
+        }
+
+        pub fn send(&mut self, text: String) {
+            self.outbox.push(text);
+            if self.socket.ready_state() != WebSocket::OPEN {
+                return;
+            }
+            for text in self.outbox.drain(..) {
+                let _ = self.socket.send_with_str(&text);
+            }
+        }
+
+        pub fn receive(&mut self) -> Vec<String> {
+            self.inbox.borrow_mut().drain(..).collect()
+        }
+    }
+}
+
+use transport::Connection;
+
+struct Sample {
+    tick: u32,
+    position: Vec2,
+    rotation: f32,
+    scale: f32,
+}
+
+#[derive(Component)]
+struct Replicated {
+    id: u64,
+}
+
+#[derive(Resource)]
+struct ClientState {
+    capture: Entity,
+    spectate: bool,
+    pilot: Option<u32>,
+    view: WorldView,
+    entities: HashMap<u64, Entity>,
+    samples: HashMap<u64, VecDeque<Sample>>,
+    latest_tick: Option<u32>,
+    render_tick: f32,
+    sent: Option<Vec<Input>>,
+}
+
+fn setup_client(world: &mut World) {
+    let settings = world.resource::<ClientSettings>().clone();
+    // the keyboard drives a controller with no ship, the server moves ours
+    let capture = world.spawn(PlayerController { id: 0 }).id();
+    world.insert_resource(ClientState {
+        capture,
+        spectate: settings.spectate,
+        pilot: None,
+        view: WorldView::new(),
+        entities: HashMap::new(),
+        samples: HashMap::new(),
+        latest_tick: None,
+        render_tick: 0.0,
+        sent: None,
+    });
+
+    match Connection::open(&settings.server) {
+        Ok(mut connection) => {
+            let join = ClientMessage::Join {
+                spectate: settings.spectate,
+            };
+            if let Ok(text) = ron::to_string(&join) {
+                connection.send(text);
+            }
+            world.insert_non_send_resource(connection);
+            info!("connected to {}", settings.server);
+        }
+        Err(e) => error!("could not connect to {}: {}", settings.server, e),
+    }
+}
+
+fn receive_frames(
+    connection: Option<NonSendMut<Connection>>,
+    mut client: ResMut<ClientState>,
+    mut score: ResMut<Score>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(mut connection) = connection else {
+        return;
+    };
+    for text in connection.receive() {
+        match ron::from_str::<ServerMessage>(&text) {
+            Ok(ServerMessage::Welcome { pilot }) => {
+                client.pilot = pilot;
+                match pilot {
+                    Some(pilot) => info!("flying ship {}", pilot),
+                    None => info!("spectating"),
+                }
+            }
+            Ok(ServerMessage::Frame(frame)) => {
+                apply(&mut client.view, &frame);
+                let client = &mut *client;
+                for id in &frame.removed {
+                    client.samples.remove(id);
+                }
+                for (id, replica) in client.view.iter() {
+                    client.samples.entry(*id).or_default().push_back(Sample {
+                        tick: frame.tick,
+                        position: replica.position,
+                        rotation: replica.rotation,
+                        scale: replica.scale,
+                    });
+                }
+                client.latest_tick = Some(frame.tick);
+                score.teams = frame.scores;
+                if frame.state != *state.get() {
+                    next_state.set(frame.state);
+                }
+            }
+            Err(e) => warn!("bad message from server: {}", e),
+        }
+    }
+}
+
+fn spawn_replica(world: &mut World, replica: &Replica) -> Option<Entity> {
+    let body = BodyState {
+        position: replica.position,
+        velocity: replica.velocity,
+        rotation: replica.rotation,
+        angular_velocity: 0.0,
+        scale: replica.scale,
+    };
+    match replica.kind {
+        Kind::Asteroid { variant } => restore_asteroid(world, &body, variant),
+        Kind::Ship { team, ship } => Some(restore_ship(
+            world,
+            &body,
+            Entity::PLACEHOLDER,
+            team,
+            ship,
+            Duration::ZERO,
+        )),
+        Kind::Bullet { team } => restore_bullet(world, &body, team, Duration::ZERO, None),
+    }
+}
+
+// the server decides what exists, mirror its view
+fn sync_replicas(world: &mut World) {
+    let (view, entities) = {
+        let client = world.resource::<ClientState>();
+        (client.view.clone(), client.entities.clone())
+    };
+
+    let mut kept = HashMap::new();
+    for (id, entity) in entities {
+        let alive = world.get_entity(entity).is_ok();
+        if view.contains_key(&id) && alive {
+            kept.insert(id, entity);
+        } else if alive {
+            world.despawn(entity);
+        }
+    }
+
+    // entities can be missing because they are new, or because game over cleared them locally
+    for (id, replica) in view.iter() {
+        if kept.contains_key(id) {
+            continue;
+        }
+        if let Some(entity) = spawn_replica(world, replica) {
+            world.entity_mut(entity).insert(Replicated { id: *id });
+            kept.insert(*id, entity);
+        }
+    }
+    world.resource_mut::<ClientState>().entities = kept;
+}
+
+// draw a little in the past, blending between the two snapshots either side
+fn interpolate(
+    time: Res<Time>,
+    grid: Option<Res<Grid>>,
+    mut client: ResMut<ClientState>,
+    mut replicas: Query<(&Replicated, &mut Position, &mut Rotation, &mut Scale)>,
+) {
+    let Some(latest) = client.latest_tick else {
+        return;
+    };
+    let target = latest as f32 - INTERPOLATION_DELAY;
+    let render_tick = client.render_tick + time.delta_secs() * TICK_RATE;
+    // catch up or fall back if the connection hiccups rather than drift further out
+    client.render_tick = if (render_tick - target).abs() > INTERPOLATION_DELAY {
+        target
+    } else {
+        render_tick
+    };
+    let render_tick = client.render_tick;
+
+    for (replicated, mut position, mut rotation, mut scale) in replicas.iter_mut() {
+        let Some(samples) = client.samples.get_mut(&replicated.id) else {
+            continue;
+        };
+        while samples.len() > 2 && (samples[1].tick as f32) < render_tick {
+            samples.pop_front();
+        }
+        let (from, to) = match (samples.front(), samples.get(1)) {
+            (Some(from), Some(to)) => (from, to),
+            (Some(only), None) => (only, only),
+            _ => continue,
+        };
+        let span = to.tick.saturating_sub(from.tick).max(1) as f32;
+        let blend = ((render_tick - from.tick as f32) / span).clamp(0.0, 1.0);
+
+        // short way round the wrap and round the circle
+        let offset = to.position - from.position;
+        let offset = grid.as_ref().map_or(offset, |grid| grid.wrap_delta(offset));
+        position.0 = from.position + offset * blend;
+        let turn = turn_between(from.rotation, to.rotation);
+        rotation.0 = from.rotation + turn * blend;
+        scale.0 = from.scale + (to.scale - from.scale) * blend;
+    }
+}
+
+fn send_inputs(
+    connection: Option<NonSendMut<Connection>>,
+    mut client: ResMut<ClientState>,
+    mut events: EventReader<InputEvent>,
+) {
+    let inputs: Vec<Input> = events
+        .read()
+        .filter(|event| event.controller == client.capture && remote_action(event.input.action))
+        .map(|event| event.input)
+        .collect();
+    let Some(mut connection) = connection else {
+        return;
+    };
+    if client.spectate || client.pilot.is_none() {
+        return;
+    }
+    // the server holds on to the last inputs, so only changes need sending
+    if client.sent.as_ref() == Some(&inputs) {
+        return;
+    }
+    if let Ok(text) = ron::to_string(&ClientMessage::Input(inputs.clone())) {
+        connection.send(text);
+        client.sent = Some(inputs);
+    }
+}
+
+fn simulated_locally() -> bool {
+    false
+}
+
+pub struct ClientPlugin {
+    pub settings: ClientSettings,
+}
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone());
+        // the server owns the game state, and nothing is simulated locally
+        app.insert_resource(StatesLocked);
+        app.configure_sets(
+            Update,
+            (
+                InGameSet::DespawnEntities,
+                InGameSet::UpdateEntities,
+                InGameSet::CollisionDetection,
+                InGameSet::CollisionReaction,
+            )
+                .run_if(simulated_locally),
+        );
+        app.add_systems(PreStartup, setup_client);
+        app.add_systems(PreUpdate, (receive_frames, sync_replicas).chain());
+        app.add_systems(Update, (send_inputs).in_set(InGameSet::GameInput));
+        app.add_systems(Update, (interpolate).before(InGameSet::RenderSetup));
+    }
+}
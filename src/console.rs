@@ -0,0 +1,358 @@
+use crate::daily::DailyRun;
+use bevy::ecs::system::SystemId;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// lines kept in the scrollback
+const LOG_LINES: usize = 12;
+
+// a command gets the words typed after its name and replies with a line for the log
+pub type CommandSystem = SystemId<In<Vec<String>>, String>;
+
+struct ConsoleCommand {
+    help: String,
+    system: CommandSystem,
+}
+
+#[derive(Resource, Default)]
+struct ConsoleCommands(BTreeMap<String, ConsoleCommand>);
+
+// one per ron config type, rereads its files and says how many
+type Reloader = fn(&World) -> usize;
+
+#[derive(Resource, Default)]
+struct ConfigReloaders(Vec<Reloader>);
+
+// set by commands that bend the rules, achievements stay locked until the next run
+#[derive(Resource, Default)]
+pub struct Cheated(pub bool);
+
+#[derive(Resource, Default)]
+struct Console {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+    history: Vec<String>,
+    // how far back up the history we are, None while typing something new
+    browsing: Option<usize>,
+    submitted: Vec<String>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+        let excess = self.log.len().saturating_sub(LOG_LINES);
+        self.log.drain(..excess);
+    }
+}
+
+#[derive(Component)]
+struct ConsolePanel;
+
+#[derive(Component)]
+struct ConsoleText;
+
+// plugins hand the console their own commands, e.g. app.add_console_command("god", "...", toggle_god)
+pub trait ConsoleExt {
+    fn add_console_command<M>(
+        &mut self,
+        name: &str,
+        help: &str,
+        system: impl IntoSystem<In<Vec<String>>, String, M> + 'static,
+    ) -> &mut Self;
+}
+
+impl ConsoleExt for App {
+    fn add_console_command<M>(
+        &mut self,
+        name: &str,
+        help: &str,
+        system: impl IntoSystem<In<Vec<String>>, String, M> + 'static,
+    ) -> &mut Self {
+        let world = self.world_mut();
+        let system = world.register_system(system);
+        world.init_resource::<ConsoleCommands>();
+        world.resource_mut::<ConsoleCommands>().0.insert(
+            name.to_string(),
+            ConsoleCommand {
+                help: help.to_string(),
+                system,
+            },
+        );
+        self
+    }
+}
+
+// plugins load their ron configs through this so "reload configs" knows about them
+pub trait ConfigExt {
+    fn add_config<A: Asset + for<'de> Deserialize<'de>>(
+        &mut self,
+        extensions: &[&'static str],
+    ) -> &mut Self;
+}
+
+impl ConfigExt for App {
+    fn add_config<A: Asset + for<'de> Deserialize<'de>>(
+        &mut self,
+        extensions: &[&'static str],
+    ) -> &mut Self {
+        self.add_plugins(RonAssetPlugin::<A>::new(extensions));
+        let world = self.world_mut();
+        world.init_resource::<ConfigReloaders>();
+        world.resource_mut::<ConfigReloaders>().0.push(reload_config::<A>);
+        self
+    }
+}
+
+fn reload_config<A: Asset>(world: &World) -> usize {
+    let asset_server = world.resource::<AssetServer>();
+    let mut reloaded = 0;
+    for id in world.resource::<Assets<A>>().ids() {
+        if let Some(path) = asset_server.get_path(id) {
+            asset_server.reload(path.into_owned());
+            reloaded += 1;
+        }
+    }
+    reloaded
+}
+
+// the longest start every candidate shares
+fn common_prefix<'a>(mut candidates: impl Iterator<Item = &'a String>) -> String {
+    let Some(first) = candidates.next() else {
+        return String::new();
+    };
+    let mut prefix = first.clone();
+    for candidate in candidates {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+fn complete(console: &mut Console, commands: &ConsoleCommands) {
+    // only the command name is completed, arguments are free form
+    if console.input.contains(' ') {
+        return;
+    }
+    let matches: Vec<&String> = commands
+        .0
+        .keys()
+        .filter(|name| name.starts_with(&console.input))
+        .collect();
+    match matches.len() {
+        0 => (),
+        1 => console.input = format!("{} ", matches[0]),
+        _ => {
+            let prefix = common_prefix(matches.iter().copied());
+            let names: Vec<&str> = matches.iter().map(|name| name.as_str()).collect();
+            console.print(names.join("  "));
+            console.input = prefix;
+        }
+    }
+}
+
+fn browse_history(console: &mut Console, older: bool) {
+    if console.history.is_empty() {
+        return;
+    }
+    let last = console.history.len() - 1;
+    console.browsing = match (console.browsing, older) {
+        (None, true) => Some(last),
+        (None, false) => None,
+        (Some(index), true) => Some(index.saturating_sub(1)),
+        (Some(index), false) if index < last => Some(index + 1),
+        (Some(_), false) => None,
+    };
+    console.input = console
+        .browsing
+        .map_or(String::new(), |index| console.history[index].clone());
+}
+
+// the console takes the keyboard while it's open, so it watches for its own key
+fn type_into_console(
+    mut console: ResMut<Console>,
+    commands: Res<ConsoleCommands>,
+    mut events: EventReader<KeyboardInput>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+) {
+    // the key that closes it shouldn't reach the game either
+    let was_open = console.open;
+    for event in events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        if event.key_code == KeyCode::Backquote {
+            console.open = !console.open;
+            continue;
+        }
+        if !console.open {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                let line = std::mem::take(&mut console.input);
+                console.browsing = None;
+                if !line.trim().is_empty() {
+                    console.history.push(line.clone());
+                    console.submitted.push(line);
+                }
+            }
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::Tab => complete(&mut console, &commands),
+            Key::ArrowUp => browse_history(&mut console, true),
+            Key::ArrowDown => browse_history(&mut console, false),
+            Key::Escape => console.open = false,
+            Key::Space => console.input.push(' '),
+            Key::Character(text) => console.input.push_str(text),
+            _ => (),
+        }
+    }
+    if was_open || console.open {
+        keyboard.reset_all();
+    }
+}
+
+fn run_commands(world: &mut World) {
+    let lines = std::mem::take(&mut world.resource_mut::<Console>().submitted);
+    for line in lines {
+        let mut words = line.split_whitespace().map(str::to_string);
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let args: Vec<String> = words.collect();
+        let system = world
+            .resource::<ConsoleCommands>()
+            .0
+            .get(&name)
+            .map(|command| command.system);
+
+        let reply = match system {
+            Some(system) => world
+                .run_system_with_input(system, args)
+                .unwrap_or_else(|e| format!("{} failed: {}", name, e)),
+            None => format!("unknown command {}, try help", name),
+        };
+        let mut console = world.resource_mut::<Console>();
+        console.print(format!("> {}", line));
+        if !reply.is_empty() {
+            console.print(reply);
+        }
+    }
+}
+
+fn show_console(
+    mut commands: Commands,
+    console: Res<Console>,
+    panels: Query<Entity, With<ConsolePanel>>,
+    mut texts: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    if !console.open {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+
+    let mut shown = console.log.join("\n");
+    shown.push_str(&format!("\n> {}_", console.input));
+    if let Ok(mut text) = texts.get_single_mut() {
+        text.0 = shown;
+        return;
+    }
+    commands
+        .spawn((
+            ConsolePanel,
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                left: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                height: Val::Percent(40.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            GlobalZIndex(20),
+        ))
+        .with_child((
+            ConsoleText,
+            Text::new(shown),
+            TextFont {
+                font_size: 16.0,
+                ..default()
+            },
+        ));
+}
+
+fn help(In(_): In<Vec<String>>, commands: Res<ConsoleCommands>) -> String {
+    commands
+        .0
+        .iter()
+        .map(|(name, command)| format!("{} - {}", name, command.help))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn clear(In(_): In<Vec<String>>, mut console: ResMut<Console>) -> String {
+    console.log.clear();
+    String::new()
+}
+
+fn timescale(
+    In(args): In<Vec<String>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut cheated: ResMut<Cheated>,
+) -> String {
+    match args.first().map(|scale| scale.parse::<f32>()) {
+        Some(Ok(scale)) if scale >= 0.0 => {
+            time.set_relative_speed(scale);
+            cheated.0 = true;
+            format!("time runs at {}x", scale)
+        }
+        _ => format!("time runs at {}x, timescale <scale>", time.relative_speed()),
+    }
+}
+
+fn reload(In(args): In<Vec<String>>, world: &mut World) -> String {
+    if args.first().map(String::as_str) != Some("configs") {
+        return "reload configs".to_string();
+    }
+    let reloaders = world.resource::<ConfigReloaders>().0.clone();
+    let count: usize = reloaders.iter().map(|reload| reload(world)).sum();
+    // meshes and materials are built once, only tuning values change live
+    format!("reloading {} configs", count)
+}
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>();
+        app.init_resource::<ConsoleCommands>();
+        app.init_resource::<ConfigReloaders>();
+        app.init_resource::<Cheated>();
+        app.add_console_command("help", "list every command", help);
+        app.add_console_command("clear", "empty the console", clear);
+        app.add_console_command("timescale", "timescale <scale>, 1 is normal speed", timescale);
+        app.add_console_command("reload", "reload configs, rereads every ron config", reload);
+        app.add_systems(
+            PreUpdate,
+            (type_into_console, run_commands)
+                .chain()
+                .after(InputSystem)
+                .run_if(not(resource_exists::<DailyRun>)),
+        );
+        app.add_systems(Update, show_console);
+    }
+}
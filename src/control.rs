@@ -15,7 +15,80 @@ impl Controller for PlayerController {
     }
 }
 
-trait Controller {
+// inputs arrive over the network, see netplay
+#[derive(Component)]
+pub struct NetController {
+    pub id: u32,
+}
+
+impl Controller for NetController {
+    fn get_id(&self) -> &u32 {
+        &self.id
+    }
+    fn get_mut_id(&mut self) -> &mut u32 {
+        &mut self.id
+    }
+}
+
+// inputs are handed in by a training harness, see gym
+#[derive(Component)]
+pub struct AgentController {
+    pub id: u32,
+}
+
+impl Controller for AgentController {
+    fn get_id(&self) -> &u32 {
+        &self.id
+    }
+    fn get_mut_id(&mut self) -> &mut u32 {
+        &mut self.id
+    }
+}
+
+// flown by the built in pilot, see ai
+#[derive(Component)]
+pub struct AiController {
+    pub id: u32,
+    // index into the levels in the ai config
+    pub skill: usize,
+}
+
+impl Controller for AiController {
+    fn get_id(&self) -> &u32 {
+        &self.id
+    }
+    fn get_mut_id(&mut self) -> &mut u32 {
+        &mut self.id
+    }
+}
+
+// plays back what a recorded controller did, see replay
+#[derive(Component)]
+pub struct ReplayController {
+    pub id: u32,
+}
+
+impl Controller for ReplayController {
+    fn get_id(&self) -> &u32 {
+        &self.id
+    }
+    fn get_mut_id(&mut self) -> &mut u32 {
+        &mut self.id
+    }
+}
+
+// the id of whichever kind of controller an entity holds
+pub fn controller_id(world: &World, entity: Entity) -> Option<u32> {
+    world
+        .get::<PlayerController>(entity)
+        .map(|controller| *controller.get_id())
+        .or_else(|| world.get::<NetController>(entity).map(|controller| *controller.get_id()))
+        .or_else(|| world.get::<AgentController>(entity).map(|controller| *controller.get_id()))
+        .or_else(|| world.get::<AiController>(entity).map(|controller| *controller.get_id()))
+        .or_else(|| world.get::<ReplayController>(entity).map(|controller| *controller.get_id()))
+}
+
+pub trait Controller {
     fn get_id(&self) -> &u32;
     fn get_mut_id(&mut self) -> &mut u32;
 }
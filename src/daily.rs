@@ -0,0 +1,304 @@
+use crate::bodies::NeedsConfig;
+use crate::console::ConfigExt;
+use crate::control::{Pawn, PlayerController, ShipPawn};
+use crate::grid::Grid;
+use crate::schedule::InGameSet;
+use crate::score::Score;
+use crate::ship::{Pilot, ShipKind};
+use crate::stats::RunStats;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// every daily result so far, kept next to the savegame
+const BOARD_PATH: &str = "daily_board.ron";
+// how many of the best days the game over screen lists
+const BOARD_SHOWN: usize = 5;
+
+// days since 1970-01-01, in UTC so everyone is on the same challenge at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Day(pub i64);
+
+impl Day {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn today() -> Self {
+        let since = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Self((since.as_secs() / 86_400) as i64)
+    }
+
+    // the browser has no clock std can read, the page's performance timer counts from the epoch
+    #[cfg(target_arch = "wasm32")]
+    pub fn today() -> Self {
+        let millis = web_sys::window()
+            .and_then(|window| window.performance())
+            .map_or(0.0, |performance| performance.time_origin() + performance.now());
+        Self((millis / 86_400_000.0) as i64)
+    }
+
+    // the same world for everyone playing on this day
+    pub fn seed(&self) -> u64 {
+        fnv1a(format!("daily {}", self).as_bytes())
+    }
+
+    // year, month, day from the day count, after Howard Hinnant's civil_from_days
+    fn civil(&self) -> (i64, u32, u32) {
+        let z = self.0 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.civil();
+        write!(f, "{}-{:02}-{:02}", year, month, day)
+    }
+}
+
+// stable across builds and platforms, unlike std's hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// the ruleset everyone flies under, mod packs are left out of daily runs
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct DailyConfig {
+    // roster indexes, one a day in turn, the ship brings its own guns
+    ships: Vec<usize>,
+    font_size: f32,
+}
+
+impl DailyConfig {
+    fn ship(&self, day: Day) -> Option<usize> {
+        if self.ships.is_empty() {
+            return None;
+        }
+        Some(self.ships[day.0.rem_euclid(self.ships.len() as i64) as usize])
+    }
+}
+
+#[derive(Resource)]
+struct DailyConfigHandle {
+    config: Handle<DailyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DailyResult {
+    day: Day,
+    seed: u64,
+    score: u64,
+    seconds: u64,
+}
+
+impl DailyResult {
+    // pasted wherever, the hash makes a typo'd or edited score stand out
+    fn share(&self) -> String {
+        let hash = fnv1a(format!("{}:{}:{}:{}", self.day.0, self.seed, self.score, self.seconds).as_bytes());
+        format!(
+            "asteroids daily {} seed {} score {} time {}:{:02} #{:08x}",
+            self.day,
+            self.seed,
+            self.score,
+            self.seconds / 60,
+            self.seconds % 60,
+            hash as u32,
+        )
+    }
+}
+
+// one scored attempt a day, whatever came first
+#[derive(Resource, Default, Serialize, Deserialize)]
+struct DailyBoard {
+    results: Vec<DailyResult>,
+}
+
+impl DailyBoard {
+    fn load() -> Self {
+        std::fs::read_to_string(BOARD_PATH)
+            .ok()
+            .and_then(|text| match ron::from_str::<DailyBoard>(&text) {
+                Ok(board) => Some(board),
+                Err(e) => {
+                    warn!("ignoring {}: {}", BOARD_PATH, e);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(BOARD_PATH, text).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("could not save the daily board: {}", e);
+        }
+    }
+
+    fn result(&self, day: Day) -> Option<&DailyResult> {
+        self.results.iter().find(|result| result.day == day)
+    }
+}
+
+// the console and saving are off while it exists, they'd make the day's run replayable
+#[derive(Resource)]
+pub struct DailyRun {
+    day: Day,
+    // the ship swapped in once the config has loaded
+    locked: bool,
+    // this run is the day's attempt, written down as soon as it starts so quitting doesn't undo it
+    counted: bool,
+}
+
+#[derive(Component)]
+struct DailyMessage;
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.daily.ron");
+    commands.insert_resource(DailyConfigHandle { config });
+}
+
+fn spawn_pilot(mut commands: Commands, mut run: ResMut<DailyRun>) {
+    let mut board = DailyBoard::load();
+    match board.result(run.day) {
+        Some(result) => info!("daily {} already played, this one is practice: {}", run.day, result.share()),
+        None => {
+            info!("daily {} seed {}", run.day, run.day.seed());
+            board.results.push(DailyResult {
+                day: run.day,
+                seed: run.day.seed(),
+                score: 0,
+                seconds: 0,
+            });
+            board.save();
+            run.counted = true;
+        }
+    }
+    commands.insert_resource(board);
+    commands.spawn((PlayerController { id: 0 }, Pilot::default()));
+}
+
+// the pilot may already have a ship by the time the config is in, swap it over like ship select does
+fn lock_ship(
+    mut commands: Commands,
+    mut run: ResMut<DailyRun>,
+    mut pilots: Query<(Entity, &mut Pilot)>,
+    ships: Query<(Entity, &ShipPawn)>,
+    configs: Res<Assets<DailyConfig>>,
+    config_handle: Res<DailyConfigHandle>,
+) {
+    if run.locked {
+        return;
+    }
+    let Some(ship) = configs
+        .get(config_handle.config.id())
+        .and_then(|config| config.ship(run.day))
+    else {
+        return;
+    };
+    for (controller, mut pilot) in pilots.iter_mut() {
+        pilot.ship = ship;
+        for (entity, pawn) in ships.iter() {
+            if pawn.get_controller() == &controller {
+                commands.entity(entity).insert((ShipKind(ship), NeedsConfig));
+            }
+        }
+    }
+    run.locked = true;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_result(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut run: ResMut<DailyRun>,
+    mut board: ResMut<DailyBoard>,
+    score: Res<Score>,
+    stats: Res<RunStats>,
+    grid: Option<Res<Grid>>,
+    configs: Res<Assets<DailyConfig>>,
+    config_handle: Res<DailyConfigHandle>,
+) {
+    let day = run.day;
+    let mut shown = match board.results.iter_mut().find(|result| result.day == day) {
+        Some(result) if run.counted => {
+            result.score = score.teams.values().sum();
+            result.seconds = stats.time.as_secs();
+            let share = result.share();
+            info!("{}", share);
+            board.save();
+            run.counted = false;
+            format!("daily {} recorded\n{}\n", day, share)
+        }
+        Some(result) => format!("practice, today's run is in\n{}\n", result.share()),
+        None => String::new(),
+    };
+
+    let mut best: Vec<&DailyResult> = board.results.iter().collect();
+    best.sort_by_key(|result| std::cmp::Reverse(result.score));
+    for result in best.iter().take(BOARD_SHOWN) {
+        shown.push_str(&format!("{}  {}\n", result.day, result.score));
+    }
+
+    let Some(grid) = grid else {
+        return;
+    };
+    let font_size = configs
+        .get(config_handle.config.id())
+        .map_or(18.0, |config| config.font_size);
+    commands.spawn((
+        DailyMessage,
+        Text2d::new(shown),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_translation(Vec3::new(
+            0.0,
+            -grid.height_half * grid.size * 3.0 / 4.0,
+            1.0,
+        )),
+    ));
+}
+
+fn despawn_message(mut commands: Commands, messages: Query<Entity, With<DailyMessage>>) {
+    for entity in messages.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+// the day's world and ship, first run of the day is the one that counts
+//   asteroids --daily
+pub struct DailyPlugin {
+    pub day: Day,
+}
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<DailyConfig>(&["daily.ron"]);
+        app.insert_resource(DailyRun {
+            day: self.day,
+            locked: false,
+            counted: false,
+        });
+        app.add_systems(PreStartup, spawn_pilot);
+        app.add_systems(Startup, load_config);
+        app.add_systems(Update, (lock_ship).in_set(InGameSet::LoadEntities));
+        app.add_systems(OnEnter(GameState::GameOver), record_result);
+        app.add_systems(OnExit(GameState::GameOver), despawn_message);
+    }
+}
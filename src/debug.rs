@@ -0,0 +1,315 @@
+use crate::asteroid::Asteroid;
+use crate::bodies::*;
+use crate::bullet::Bullet;
+use crate::grid::{CameraFocus, Grid};
+use crate::input_actions::*;
+use crate::particles::Particle;
+use crate::powerup::Pickup;
+use crate::schedule::InGameSet;
+use crate::ship::Ship;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use std::fmt::Write;
+
+const RADIUS_COLOR: Color = Color::srgb(0.2, 0.9, 0.3);
+const HOVER_COLOR: Color = Color::srgb(1.0, 0.8, 0.1);
+const VELOCITY_COLOR: Color = Color::srgb(0.3, 0.6, 1.0);
+const COLLISION_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+const PLAYFIELD_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const WRAP_COLOR: Color = Color::srgb(0.8, 0.3, 0.8);
+// seconds of travel each velocity arrow shows
+const VELOCITY_SCALE: f32 = 0.5;
+// bullets are tiny, give the cursor something to aim at, in grid units
+const PICK_RADIUS: f32 = 0.15;
+
+#[derive(Resource, Default)]
+struct DebugOverlay {
+    enabled: bool,
+    // the body under the cursor, if any
+    hovered: Option<Entity>,
+}
+
+#[derive(Component)]
+struct DebugPanel;
+
+type Inspected<'a> = (
+    &'a Position,
+    &'a Rotation,
+    &'a Scale,
+    Option<&'a Velocity>,
+    Option<&'a AngularVelocity>,
+    Option<&'a Acceleration>,
+    Option<&'a RigidBody>,
+    Option<&'a Collider>,
+    Has<Asteroid>,
+    Has<Ship>,
+    Has<Bullet>,
+);
+
+fn overlay_enabled(overlay: Res<DebugOverlay>) -> bool {
+    overlay.enabled
+}
+
+fn toggle_overlay(
+    mut commands: Commands,
+    mut overlay: ResMut<DebugOverlay>,
+    mut events: EventReader<InputEvent>,
+    panels: Query<Entity, With<DebugPanel>>,
+) {
+    for event in events.read() {
+        if event.input.action != Actions::ToggleDebug {
+            continue;
+        }
+        overlay.enabled = !overlay.enabled;
+        if overlay.enabled {
+            commands
+                .spawn((
+                    DebugPanel,
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(8.0),
+                        top: Val::Px(8.0),
+                        padding: UiRect::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                    GlobalZIndex(10),
+                ))
+                .with_child((
+                    Text::new(""),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                ));
+        } else {
+            overlay.hovered = None;
+            for panel in panels.iter() {
+                commands.entity(panel).despawn_recursive();
+            }
+        }
+    }
+}
+
+// view space pixels for a grid position, the same place project_positions puts it
+fn to_view(grid: &Grid, focus: &CameraFocus, position: Vec2) -> Vec2 {
+    grid.project(position, focus.0)
+}
+
+fn pick_hovered(
+    grid: Res<Grid>,
+    focus: Res<CameraFocus>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    bodies: Query<(Entity, &Position, &RigidBody)>,
+    mut overlay: ResMut<DebugOverlay>,
+) {
+    overlay.hovered = None;
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Ok(view) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+    let point = view / grid.size + focus.0;
+
+    overlay.hovered = bodies
+        .iter()
+        .map(|(entity, position, body)| {
+            let distance = grid.wrap_delta(position.0 - point).length();
+            (entity, distance, body.radius.max(PICK_RADIUS))
+        })
+        .filter(|(_, distance, reach)| distance < reach)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _, _)| entity);
+}
+
+fn draw_bodies(
+    mut gizmos: Gizmos,
+    grid: Res<Grid>,
+    focus: Res<CameraFocus>,
+    overlay: Res<DebugOverlay>,
+    bodies: Query<(Entity, &Position, &RigidBody, Option<&Velocity>)>,
+) {
+    for (entity, position, body, velocity) in bodies.iter() {
+        let center = to_view(&grid, &focus, position.0);
+        let color = if overlay.hovered == Some(entity) {
+            HOVER_COLOR
+        } else {
+            RADIUS_COLOR
+        };
+        gizmos.circle_2d(
+            Isometry2d::from_translation(center),
+            body.radius * grid.size,
+            color,
+        );
+        if let Some(velocity) = velocity {
+            let tip = center + velocity.0 * VELOCITY_SCALE * grid.size;
+            gizmos.arrow_2d(center, tip, VELOCITY_COLOR);
+        }
+    }
+}
+
+// the edge of the playfield, and further out where bodies actually wrap
+fn draw_grid(mut gizmos: Gizmos, grid: Res<Grid>, focus: Res<CameraFocus>) {
+    // the world doesn't wrap around the camera, the origin moves with the focus
+    let center = -focus.0 * grid.size;
+    let playfield = Vec2::new(grid.width_half, grid.height_half) * 2.0 * grid.size;
+    gizmos.rect_2d(
+        Isometry2d::from_translation(center),
+        playfield,
+        PLAYFIELD_COLOR,
+    );
+    gizmos.rect_2d(
+        Isometry2d::from_translation(center),
+        grid.wrap_size() * grid.size,
+        WRAP_COLOR,
+    );
+}
+
+fn draw_collisions(
+    mut gizmos: Gizmos,
+    grid: Res<Grid>,
+    focus: Res<CameraFocus>,
+    positions: Query<&Position>,
+    mut collisions: EventReader<Collision>,
+) {
+    for event in collisions.read() {
+        if let (Ok(a), Ok(b)) = (positions.get(event.entity1), positions.get(event.entity2)) {
+            let start = to_view(&grid, &focus, a.0);
+            // the pair may touch across the seam, draw the short way
+            let end = start + grid.wrap_delta(b.0 - a.0) * grid.size;
+            gizmos.line_2d(start, end, COLLISION_COLOR);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_panel(
+    overlay: Res<DebugOverlay>,
+    diagnostics: Res<DiagnosticsStore>,
+    everything: Query<()>,
+    asteroids: Query<(), With<Asteroid>>,
+    ships: Query<(), With<Ship>>,
+    bullets: Query<(), With<Bullet>>,
+    pickups: Query<(), With<Pickup>>,
+    particles: Query<(), With<Particle>>,
+    inspected: Query<Inspected>,
+    panels: Query<&Children, With<DebugPanel>>,
+    mut texts: Query<&mut Text>,
+) {
+    let mut report = String::new();
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed());
+    match fps {
+        Some(fps) => {
+            let _ = writeln!(report, "fps {:.0}", fps);
+        }
+        None => report.push_str("fps -\n"),
+    }
+    let _ = writeln!(
+        report,
+        "entities {}\nasteroids {}\nships {}\nbullets {}\npickups {}\nparticles {}",
+        everything.iter().count(),
+        asteroids.iter().count(),
+        ships.iter().count(),
+        bullets.iter().count(),
+        pickups.iter().count(),
+        particles.iter().count(),
+    );
+
+    let hovered = overlay.hovered.and_then(|entity| {
+        inspected
+            .get(entity)
+            .ok()
+            .map(|components| (entity, components))
+    });
+    if let Some((entity, components)) = hovered {
+        let (
+            position,
+            rotation,
+            scale,
+            velocity,
+            angular_velocity,
+            acceleration,
+            body,
+            collider,
+            asteroid,
+            ship,
+            bullet,
+        ) = components;
+        let kind = if asteroid {
+            "asteroid"
+        } else if ship {
+            "ship"
+        } else if bullet {
+            "bullet"
+        } else {
+            "body"
+        };
+        let _ = writeln!(report, "\n{} {}", kind, entity);
+        let _ = writeln!(report, "position {:.2} {:.2}", position.0.x, position.0.y);
+        let _ = writeln!(report, "rotation {:.2}", rotation.0);
+        let _ = writeln!(report, "scale {:.2}", scale.0);
+        if let Some(velocity) = velocity {
+            let _ = writeln!(report, "velocity {:.2} {:.2}", velocity.0.x, velocity.0.y);
+        }
+        if let Some(angular_velocity) = angular_velocity {
+            let _ = writeln!(report, "angular velocity {:.2}", angular_velocity.0);
+        }
+        if let Some(acceleration) = acceleration {
+            let _ = writeln!(
+                report,
+                "acceleration {:.2} {:.2}",
+                acceleration.0.x, acceleration.0.y
+            );
+        }
+        if let Some(body) = body {
+            let _ = writeln!(report, "radius {:.3} mass {:.2}", body.radius, body.mass);
+        }
+        if let Some(collider) = collider {
+            let _ = writeln!(report, "team {}", collider.team);
+        }
+    }
+
+    for children in panels.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.0.clone_from(&report);
+            }
+        }
+    }
+}
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        app.init_resource::<DebugOverlay>();
+        app.add_systems(Update, (toggle_overlay).in_set(InGameSet::GameInput));
+        // drawn while paused too, that's when there's time to look
+        app.add_systems(
+            Update,
+            (
+                pick_hovered,
+                draw_bodies,
+                draw_grid,
+                draw_collisions,
+                update_panel,
+            )
+                .chain()
+                .after(InGameSet::RenderSetup)
+                .run_if(overlay_enabled.and(resource_exists::<Grid>)),
+        );
+    }
+}
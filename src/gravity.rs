@@ -0,0 +1,230 @@
+use crate::bodies::*;
+use crate::console::ConfigExt;
+use crate::grid::Grid;
+use crate::schedule::InGameSet;
+use crate::powerup::ExtraLives;
+use crate::ship::{spend_life, Invulnerable, Ship, ShipDestroyed};
+use crate::GameState;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy)]
+struct WellDef {
+    // grid units from the middle of the playfield
+    position: (f32, f32),
+    // pulls when positive, a black hole, pushes when negative, a white hole
+    strength: f32,
+    // grid units, anything that gets this close is gone, 0 for none
+    horizon: f32,
+    // grid units, how big it's drawn
+    size: f32,
+    color: (f32, f32, f32),
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct GravityConfig {
+    // the pull of a strength 1 well one grid unit away, in grid units per second squared
+    gravity: f32,
+    // grid units, keeps the pull finite right on top of a well
+    softening: f32,
+    // placed at the start of every run
+    wells: Vec<WellDef>,
+}
+
+#[derive(Resource)]
+struct GravityConfigHandle {
+    config: Handle<GravityConfig>,
+}
+
+#[derive(Component)]
+pub struct Well {
+    pub strength: f32,
+    pub horizon: f32,
+}
+
+// anything can open a well mid run, the config's wells come through here too
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SpawnWell {
+    // grid units from the middle of the playfield
+    pub position: Vec2,
+    pub strength: f32,
+    pub horizon: f32,
+    // grid units
+    pub size: f32,
+    pub color: Color,
+}
+
+// wells go in once a run, so a config reloaded from the console takes over on the next one
+#[derive(Resource, Default)]
+struct WellsPlaced(bool);
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.gravity.ron");
+    commands.insert_resource(GravityConfigHandle { config });
+}
+
+fn place_wells(
+    mut placed: ResMut<WellsPlaced>,
+    mut spawns: EventWriter<SpawnWell>,
+    configs: Res<Assets<GravityConfig>>,
+    config_handle: Res<GravityConfigHandle>,
+) {
+    if placed.0 {
+        return;
+    }
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    for well in config.wells.iter() {
+        spawns.send(SpawnWell {
+            position: Vec2::new(well.position.0, well.position.1),
+            strength: well.strength,
+            horizon: well.horizon,
+            size: well.size,
+            color: Color::srgb(well.color.0, well.color.1, well.color.2),
+        });
+    }
+    placed.0 = true;
+}
+
+fn spawn_wells(
+    mut commands: Commands,
+    mut events: EventReader<SpawnWell>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    grid: Res<Grid>,
+) {
+    let mut mesh = None;
+    for event in events.read() {
+        let mesh = mesh.get_or_insert_with(|| meshes.add(Circle::new(1.0)));
+        commands.spawn((
+            Well {
+                strength: event.strength,
+                horizon: event.horizon,
+            },
+            Position(event.position),
+            Rotation(0.0),
+            Scale(event.size * grid.size),
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(materials.add(event.color)),
+            Transform::default(),
+        ));
+    }
+}
+
+// anything that moves and can be touched, ghosts and popups aren't bodies
+type FreeBody = (With<RigidBody>, Without<Well>);
+
+// inverse square toward the nearest copy of each well, the same for every mass
+fn pull_bodies(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    wells: Query<(&Well, &Position)>,
+    mut bodies: Query<(&Position, &mut Velocity), FreeBody>,
+    configs: Res<Assets<GravityConfig>>,
+    config_handle: Res<GravityConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let softening = config.softening * config.softening;
+    for (position, mut velocity) in bodies.iter_mut() {
+        let mut acceleration = Vec2::ZERO;
+        for (well, well_position) in wells.iter() {
+            let delta = grid.wrap_delta(well_position.0 - position.0);
+            let distance_squared = delta.length_squared().max(softening);
+            acceleration += delta.normalize_or_zero() * config.gravity * well.strength / distance_squared;
+        }
+        velocity.0 += acceleration * time.delta_secs();
+    }
+}
+
+type Swallowed<'a> = (
+    Entity,
+    &'a mut Position,
+    &'a mut Velocity,
+    Option<&'a Rotation>,
+    Option<&'a Scale>,
+    Has<Ship>,
+    Option<&'a mut ExtraLives>,
+);
+
+// nothing comes back out, not even a shielded ship, rocks are swallowed whole without scoring
+// a ship with a life to spare is thrown clear instead, the same as any other hit
+fn swallow_bodies(
+    mut commands: Commands,
+    grid: Option<Res<Grid>>,
+    wells: Query<(&Well, &Position)>,
+    mut bodies: Query<Swallowed, FreeBody>,
+    mut destroyed: EventWriter<ShipDestroyed>,
+    mut next_state: ResMut<NextState<GameState>>,
+    invulnerable: Res<Invulnerable>,
+) {
+    let Some(grid) = grid else {
+        return;
+    };
+    for (entity, mut position, mut velocity, rotation, scale, ship, lives) in bodies.iter_mut() {
+        let swallowed_by = wells.iter().find(|(well, well_position)| {
+            grid.wrap_delta(well_position.0 - position.0).length() < well.horizon
+        });
+        let Some((_, well_position)) = swallowed_by else {
+            continue;
+        };
+        if ship {
+            if invulnerable.0 {
+                continue;
+            }
+            if spend_life(&mut commands, entity, lives) {
+                // the far corner of the playfield is as far from the well as it gets
+                position.0 = well_position.0 + Vec2::new(grid.width_half, grid.height_half);
+                velocity.0 = Vec2::ZERO;
+                continue;
+            }
+            destroyed.send(ShipDestroyed {
+                position: position.0,
+                velocity: velocity.0,
+                rotation: rotation.map_or(0.0, |rotation| rotation.0),
+                scale: scale.map_or(1.0, |scale| scale.0),
+            });
+            next_state.set(GameState::GameOver);
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+fn remove_wells(
+    mut commands: Commands,
+    mut placed: ResMut<WellsPlaced>,
+    wells: Query<Entity, With<Well>>,
+) {
+    for entity in wells.iter() {
+        commands.entity(entity).despawn();
+    }
+    placed.0 = false;
+}
+
+pub struct GravityPlugin;
+
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<GravityConfig>(&["gravity.ron"]);
+        app.add_event::<SpawnWell>();
+        app.init_resource::<WellsPlaced>();
+        app.add_systems(Startup, load_config);
+        app.add_systems(
+            Update,
+            (place_wells, spawn_wells)
+                .chain()
+                .run_if(resource_exists::<Grid>)
+                .in_set(InGameSet::LoadEntities),
+        );
+        app.add_systems(Update, (swallow_bodies).in_set(InGameSet::DespawnEntities));
+        app.add_systems(
+            Update,
+            (pull_bodies)
+                .before(update_velocity)
+                .in_set(InGameSet::UpdateEntities),
+        );
+        app.add_systems(OnExit(GameState::GameOver), remove_wells);
+    }
+}
@@ -1,6 +1,7 @@
-use crate::{bodies::*, schedule::InGameSet};
+use crate::{bodies::*, console::ConfigExt, schedule::InGameSet, GameState};
 use bevy::{prelude::*, window::WindowResized};
 use serde::Deserialize;
+use std::collections::HashSet;
 
 // because coords staring in center, half height and with make much more sense
 #[derive(Resource)]
@@ -11,22 +12,154 @@ pub struct Grid {
     pub width_half: f32,
 }
 
+impl Grid {
+    // objects wrap at the extends, not at the screen edge
+    pub fn wrap_size(&self) -> Vec2 {
+        Vec2::new(
+            self.width_half * 2.0 + (2.0 * self.extends),
+            self.height_half * 2.0 + (2.0 * self.extends),
+        )
+    }
+
+    // shortest offset between two points on the torus
+    pub fn wrap_delta(&self, delta: Vec2) -> Vec2 {
+        let size = self.wrap_size();
+        Vec2::new(
+            wrap_around(delta.x, -size.x * 0.5, size.x),
+            wrap_around(delta.y, -size.y * 0.5, size.y),
+        )
+    }
+
+    // grid position to pixels, drawn relative to what the camera is looking at
+    pub fn project(&self, position: Vec2, focus: Vec2) -> Vec2 {
+        self.wrap_delta(position - focus) * self.size
+    }
+}
+
+// the point of the world at the center of the screen, only moves in a scrolling playfield
+#[derive(Resource, Default)]
+pub struct CameraFocus(pub Vec2);
+
+// copy of a body drawn on the far side of the wrap seam
+#[derive(Component)]
+struct Ghost {
+    source: Entity,
+    offset: IVec2,
+}
+
+// how the playfield relates to the window
+#[derive(Deserialize, Resource, Clone, Copy, PartialEq)]
+pub enum Playfield {
+    // fixed size in grid units, scaled to fit the window with bars over the spare space
+    Fixed { width: f32, height: f32 },
+    // the playfield grows and shrinks with the window, for casual play
+    MatchWindow,
+    // a world bigger than the screen, the view is letterboxed like a fixed playfield
+    Scrolling {
+        width: f32,
+        height: f32,
+        view_width: f32,
+        view_height: f32,
+    },
+}
+
+#[derive(Deserialize, Asset, Clone, Copy, TypePath)]
+struct GridConfig {
+    size: f32,
+    extends: f32,
+    playfield: Playfield,
+}
+
+#[derive(Resource)]
+struct GridConfigHandle {
+    config: Handle<GridConfig>,
+}
+
+#[derive(Component)]
+struct Letterbox;
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.grid.ron");
+    commands.insert_resource(GridConfigHandle { config });
+}
+
 // so velocity numbers make sense
-fn grid_build(mut commands: Commands, window: Query<&Window>) {
-    if let Ok(window) = window.get_single() {
-        let window_height = window.resolution.height();
-        let window_width = window.resolution.width();
-        let window_scale = window.resolution.scale_factor();
-
-        //TODO! convert to config
-        let size = 100.0 * window_scale;
-        commands.insert_resource(Grid {
-            size,
-            extends: 0.5 * window_scale,
-            height_half: window_height * 0.5 / size,
-            width_half: window_width * 0.5 / size,
-        });
+fn grid_build(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut cameras: Query<&mut OrthographicProjection, With<Camera2d>>,
+    window: Query<&Window>,
+    configs: Res<Assets<GridConfig>>,
+    config_handle: Res<GridConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+
+    let fixed = match config.playfield {
+        Playfield::Fixed { width, height } => Some((width, height, width, height)),
+        Playfield::Scrolling {
+            width,
+            height,
+            view_width,
+            view_height,
+        } => Some((width, height, view_width, view_height)),
+        Playfield::MatchWindow => None,
+    };
+
+    match fixed {
+        Some((width, height, view_width, view_height)) => {
+            let grid = Grid {
+                size: config.size,
+                extends: config.extends,
+                height_half: height * 0.5,
+                width_half: width * 0.5,
+            };
+
+            // always show the whole view, whatever the window aspect
+            let view = Vec2::new(view_width, view_height) * grid.size;
+            for mut projection in cameras.iter_mut() {
+                projection.scaling_mode = bevy::render::camera::ScalingMode::AutoMin {
+                    min_width: view.x,
+                    min_height: view.y,
+                };
+            }
+
+            // cover whatever is left over, big enough for any sane aspect ratio
+            let bar = view * 4.0;
+            let mesh = meshes.add(Rectangle::new(bar.x, bar.y));
+            let material = materials.add(Color::BLACK);
+            for side in [Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y] {
+                let center = side * (view + bar) * 0.5;
+                commands.spawn((
+                    Letterbox,
+                    Mesh2d(mesh.clone()),
+                    MeshMaterial2d(material.clone()),
+                    Transform::from_translation(center.extend(5.0)),
+                ));
+            }
+            commands.insert_resource(grid);
+        }
+        None => {
+            // nothing to match headless or in the gym, play on what a default window would show
+            let resolution = window
+                .get_single()
+                .map_or_else(|_| Window::default().resolution, |window| window.resolution.clone());
+            let window_height = resolution.height();
+            let window_width = resolution.width();
+            let window_scale = resolution.scale_factor();
+
+            let size = config.size * window_scale;
+            commands.insert_resource(Grid {
+                size,
+                extends: config.extends * window_scale,
+                height_half: window_height * 0.5 / size,
+                width_half: window_width * 0.5 / size,
+            });
+        }
     }
+    commands.insert_resource(config.playfield);
 }
 
 fn grid_update(width: f32, height: f32, grid: &mut ResMut<Grid>) {
@@ -34,9 +167,16 @@ fn grid_update(width: f32, height: f32, grid: &mut ResMut<Grid>) {
     grid.height_half = height * 0.5 / grid.size;
 }
 
-fn on_resize(mut resize_reader: EventReader<WindowResized>, mut grid: ResMut<Grid>) {
-    for e in resize_reader.read() {
-        grid_update(e.width, e.height, &mut grid);
+// a fixed playfield is handled by the camera scaling, only a matched one changes size
+fn on_resize(
+    mut resize_reader: EventReader<WindowResized>,
+    grid: Option<ResMut<Grid>>,
+    playfield: Option<Res<Playfield>>,
+) {
+    if let (Some(mut grid), Some(Playfield::MatchWindow)) = (grid, playfield.as_deref()) {
+        for e in resize_reader.read() {
+            grid_update(e.width, e.height, &mut grid);
+        }
     }
 }
 
@@ -47,14 +187,11 @@ fn spawn_camera(mut commands: Commands) {
 fn project_positions(
     mut positionables: Query<(&mut Transform, &Position, &Rotation, &Scale)>,
     grid: Res<Grid>,
+    focus: Res<CameraFocus>,
 ) {
     for (mut transform, position, rotation, scale) in &mut positionables {
-        let mut new_position = position.0;
-        new_position.x *= grid.size;
-        new_position.y *= grid.size;
-
-        //wrap objects around the screen
-        transform.translation = new_position.extend(0.);
+        //wrap objects around the camera
+        transform.translation = grid.project(position.0, focus.0).extend(0.);
 
         transform.rotation = Quat::from_rotation_z(rotation.0);
 
@@ -62,6 +199,94 @@ fn project_positions(
     }
 }
 
+// which wrapped copies a body needs, one per seam it overlaps plus the corner if it overlaps both
+fn ghost_offsets(position: Vec2, radius: f32, grid: &Grid) -> Vec<IVec2> {
+    let edge = Vec2::new(grid.width_half, grid.height_half) + grid.extends;
+    let seam = |value: f32, edge: f32| {
+        if value + radius > edge {
+            -1
+        } else if value - radius < -edge {
+            1
+        } else {
+            0
+        }
+    };
+    let x = seam(position.x, edge.x);
+    let y = seam(position.y, edge.y);
+
+    let mut offsets = Vec::with_capacity(3);
+    if x != 0 {
+        offsets.push(IVec2::new(x, 0));
+    }
+    if y != 0 {
+        offsets.push(IVec2::new(0, y));
+    }
+    if x != 0 && y != 0 {
+        offsets.push(IVec2::new(x, y));
+    }
+    offsets
+}
+
+// what a ghost copies from the body it stands in for
+type Haunted<'a> = (
+    Entity,
+    &'a Position,
+    &'a RigidBody,
+    &'a Transform,
+    &'a Mesh2d,
+    &'a MeshMaterial2d<ColorMaterial>,
+);
+
+fn update_ghosts(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    focus: Res<CameraFocus>,
+    bodies: Query<Haunted, Without<Ghost>>,
+    mut ghosts: Query<(Entity, &Ghost, &mut Transform)>,
+) {
+    let mut needed = HashSet::new();
+    for (entity, position, body, _, _, _) in &bodies {
+        // the seam sits opposite the camera, so work in view space
+        let relative = grid.wrap_delta(position.0 - focus.0);
+        for offset in ghost_offsets(relative, body.radius, &grid) {
+            needed.insert((entity, offset));
+        }
+    }
+
+    let shift = |offset: IVec2| (offset.as_vec2() * grid.wrap_size() * grid.size).extend(0.);
+
+    // keep ghosts that are still overlapping in sync, drop the rest
+    for (entity, ghost, mut transform) in ghosts.iter_mut() {
+        if !needed.remove(&(ghost.source, ghost.offset)) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Ok((_, _, _, source, _, _)) = bodies.get(ghost.source) {
+            *transform = *source;
+            transform.translation += shift(ghost.offset);
+        }
+    }
+
+    for (source, offset) in needed {
+        if let Ok((_, _, _, transform, mesh, material)) = bodies.get(source) {
+            let mut transform = *transform;
+            transform.translation += shift(offset);
+            commands.spawn((
+                Ghost { source, offset },
+                mesh.clone(),
+                material.clone(),
+                transform,
+            ));
+        }
+    }
+}
+
+fn despawn_ghosts(mut commands: Commands, ghosts: Query<Entity, With<Ghost>>) {
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
 fn wrap_obj(mut obj: Query<&mut Position>, grid: Res<Grid>) {
     for mut position in &mut obj {
         position.0.x = wrap_around(
@@ -87,9 +312,24 @@ pub struct GridPlugin;
 
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (spawn_camera, grid_build));
+        app.add_config::<GridConfig>(&["grid.ron"]);
+        app.init_resource::<CameraFocus>();
+        app.add_systems(Startup, (spawn_camera, load_config));
+        // built once, the playfield it leaves behind marks it done
+        app.add_systems(
+            Update,
+            (grid_build)
+                .run_if(not(resource_exists::<Playfield>))
+                .in_set(InGameSet::LoadEntities),
+        );
         app.add_systems(Update, (wrap_obj).in_set(InGameSet::UpdateEntities));
         app.add_systems(Update, (on_resize).in_set(InGameSet::MenuInput));
-        app.add_systems(Update, (project_positions).in_set(InGameSet::RenderSetup));
+        app.add_systems(
+            Update,
+            (project_positions, update_ghosts)
+                .chain()
+                .in_set(InGameSet::RenderSetup),
+        );
+        app.add_systems(OnEnter(GameState::GameOver), despawn_ghosts);
     }
 }
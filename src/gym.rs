@@ -0,0 +1,400 @@
+use crate::achievements::RunProgress;
+use crate::asteroid::Asteroid;
+use crate::bodies::*;
+use crate::control::{AgentController, Pawn, ShipPawn};
+use crate::grid::Grid;
+use crate::input_actions::*;
+use crate::netplay::ready;
+use crate::save::{restore_snapshot, Snapshot, SAVE_VERSION};
+use crate::schedule::InGameSet;
+use crate::score::Scored;
+use crate::ship::{spawn_ship, Pilot, Ship};
+use crate::stats::RunStats;
+use crate::states::StatesLocked;
+use crate::{headless_plugins, AsteroidsGamePlugin, GameState};
+use bevy::app::PluginsState;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::tasks::tick_global_task_pools_on_main_thread;
+use bevy::time::TimeUpdateStrategy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+// every frame is the same length, however fast the trainer calls step
+const FRAME: Duration = Duration::from_nanos(16_666_667);
+// configs and meshes load in the background, give up if they never turn up
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+// the agent steers, starting and ending games is up to the trainer
+const AGENT_ACTIONS: [Actions; 3] = [Actions::MoveForward, Actions::Rotate, Actions::Shoot];
+
+// position, velocity, sin and cos of the heading, angular velocity, alive
+pub const SHIP_FEATURES: usize = 8;
+// offset and velocity relative to the ship, the short way round the wrap, then radius and present
+pub const ASTEROID_FEATURES: usize = 6;
+
+// ship features then the nearest asteroids closest first, always observation_size long
+pub type Observation = Vec<f32>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvConfig {
+    // asteroids in the observation, padded with zeros when there are fewer
+    pub nearest: usize,
+    // frames per step, the actions are held for all of them
+    pub frame_skip: u32,
+    // taken off the reward on the step the ship is destroyed
+    pub death_penalty: f32,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            nearest: 8,
+            frame_skip: 4,
+            death_penalty: 10.0,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct AgentInputs(Vec<Input>);
+
+// points scored since the step began
+#[derive(Resource, Default)]
+struct Reward(f32);
+
+fn emit_agent_inputs(
+    inputs: Res<AgentInputs>,
+    agents: Query<Entity, With<AgentController>>,
+    mut writer: EventWriter<InputEvent>,
+) {
+    for agent in agents.iter() {
+        for input in inputs.0.iter() {
+            writer.send(InputEvent {
+                controller: agent,
+                input: *input,
+            });
+        }
+    }
+}
+
+// what a kill was worth before the combo multiplier, so rewards don't swing with the combo
+fn collect_reward(mut reward: ResMut<Reward>, mut events: EventReader<Scored>) {
+    for event in events.read() {
+        reward.0 += event.points as f32;
+    }
+}
+
+// the game run headless one fixed frame at a time, for training bots against
+pub struct Env {
+    app: App,
+    config: EnvConfig,
+    agent: Entity,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Result<Self, String> {
+        let mut app = App::new();
+        app.add_plugins(headless_plugins())
+            .add_plugins(AsteroidsGamePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+            .insert_resource(StatesLocked)
+            .init_resource::<AgentInputs>()
+            .init_resource::<Reward>()
+            .add_systems(Update, (emit_agent_inputs).in_set(InGameSet::GameInput))
+            .add_systems(Update, (collect_reward).in_set(InGameSet::UpdateEntities));
+        // in place before startup so no keyboard player is added
+        let agent = app
+            .world_mut()
+            .spawn((AgentController { id: 0 }, Pilot::default()))
+            .id();
+
+        while app.plugins_state() == PluginsState::Adding {
+            tick_global_task_pools_on_main_thread();
+        }
+        app.finish();
+        app.cleanup();
+
+        let started = Instant::now();
+        while !ready(app.world()) {
+            if started.elapsed() > LOAD_TIMEOUT {
+                return Err("timed out loading the game assets".to_string());
+            }
+            app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        Ok(Self { app, config, agent })
+    }
+
+    // before the first reset, the observation size follows nearest
+    pub fn set_config(&mut self, config: EnvConfig) {
+        self.config = config;
+    }
+
+    pub fn observation_size(&self) -> usize {
+        SHIP_FEATURES + self.config.nearest * ASTEROID_FEATURES
+    }
+
+    // a fresh game, the same seed always spawns the same asteroids
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let world = self.app.world_mut();
+        if *world.resource::<State<GameState>>().get() == GameState::GameOver {
+            world
+                .resource_mut::<NextState<GameState>>()
+                .set(GameState::InGame);
+            world.run_schedule(StateTransition);
+        } else {
+            // the ship may have died on the last step, with game over still to come
+            world.resource_mut::<NextState<GameState>>().reset();
+        }
+
+        restore_snapshot(
+            world,
+            &Snapshot {
+                version: SAVE_VERSION,
+                scores: BTreeMap::new(),
+                players: Vec::new(),
+                combos: Vec::new(),
+                stats: RunStats::default(),
+                progress: RunProgress::default(),
+                seed,
+                spawn_timer: Duration::ZERO,
+                entities: Vec::new(),
+            },
+        );
+        if let Err(e) = world.run_system_once(spawn_ship) {
+            warn!("gym could not spawn the ship: {}", e);
+        }
+        world.resource_mut::<AgentInputs>().0.clear();
+        world.resource_mut::<Reward>().0 = 0.0;
+        self.observe()
+    }
+
+    pub fn step(&mut self, actions: &[Input]) -> (Observation, f32, bool) {
+        let world = self.app.world_mut();
+        world.resource_mut::<AgentInputs>().0 = actions
+            .iter()
+            .filter(|input| AGENT_ACTIONS.contains(&input.action))
+            .copied()
+            .collect();
+        world.resource_mut::<Reward>().0 = 0.0;
+
+        let mut done = false;
+        for _ in 0..self.config.frame_skip.max(1) {
+            self.app.update();
+            if self.ship().is_none() {
+                done = true;
+                break;
+            }
+        }
+
+        let mut reward = self.app.world().resource::<Reward>().0;
+        if done {
+            reward -= self.config.death_penalty;
+        }
+        (self.observe(), reward, done)
+    }
+
+    // position, velocity, rotation and angular velocity of the agent's ship
+    fn ship(&mut self) -> Option<(Vec2, Vec2, f32, f32)> {
+        let world = self.app.world_mut();
+        let mut ships = world.query_filtered::<(
+            &ShipPawn,
+            &Position,
+            &Velocity,
+            &Rotation,
+            &AngularVelocity,
+        ), With<Ship>>();
+        ships
+            .iter(world)
+            .find(|(pawn, ..)| *pawn.get_controller() == self.agent)
+            .map(|(_, position, velocity, rotation, angular_velocity)| {
+                (position.0, velocity.0, rotation.0, angular_velocity.0)
+            })
+    }
+
+    fn observe(&mut self) -> Observation {
+        let size = self.observation_size();
+        let mut observation = Vec::with_capacity(size);
+        let Some((position, velocity, rotation, angular_velocity)) = self.ship() else {
+            observation.resize(size, 0.0);
+            return observation;
+        };
+        observation.extend([
+            position.x,
+            position.y,
+            velocity.x,
+            velocity.y,
+            rotation.sin(),
+            rotation.cos(),
+            angular_velocity,
+            1.0,
+        ]);
+
+        let world = self.app.world_mut();
+        let mut asteroids =
+            world.query_filtered::<(&Position, &Velocity, &RigidBody), With<Asteroid>>();
+        let grid = world.get_resource::<Grid>();
+        let mut nearby: Vec<[f32; ASTEROID_FEATURES]> = asteroids
+            .iter(world)
+            .map(|(asteroid, asteroid_velocity, body)| {
+                let offset = asteroid.0 - position;
+                let offset = grid.map_or(offset, |grid| grid.wrap_delta(offset));
+                let relative = asteroid_velocity.0 - velocity;
+                [offset.x, offset.y, relative.x, relative.y, body.radius, 1.0]
+            })
+            .collect();
+        nearby.sort_by(|a, b| {
+            Vec2::new(a[0], a[1])
+                .length_squared()
+                .total_cmp(&Vec2::new(b[0], b[1]).length_squared())
+        });
+        nearby.resize(self.config.nearest, [0.0; ASTEROID_FEATURES]);
+        observation.extend(nearby.into_iter().flatten());
+        observation
+    }
+}
+
+// one json object per line each way, e.g.
+//   {"reset":{"seed":7}}
+//   {"step":{"actions":[{"action":"MoveForward","value":1.0},{"action":"Shoot","value":1.0}]}}
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Reset { seed: u64 },
+    Step { actions: Vec<Input> },
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response {
+    // sent once up front so the trainer can size its network
+    Spec {
+        observation_size: usize,
+        ship_features: usize,
+        asteroid_features: usize,
+        nearest: usize,
+        frame_skip: u32,
+    },
+    Step {
+        observation: Observation,
+        reward: f32,
+        done: bool,
+    },
+    Error {
+        error: String,
+    },
+}
+
+// drive the env from a trainer running us as a subprocess, until it closes our input
+pub fn serve(env: &mut Env, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    let spec = Response::Spec {
+        observation_size: env.observation_size(),
+        ship_features: SHIP_FEATURES,
+        asteroid_features: ASTEROID_FEATURES,
+        nearest: env.config.nearest,
+        frame_skip: env.config.frame_skip,
+    };
+    writeln!(output, "{}", serde_json::to_string(&spec)?)?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Reset { seed }) => Response::Step {
+                observation: env.reset(seed),
+                reward: 0.0,
+                done: false,
+            },
+            Ok(Request::Step { actions }) => {
+                let (observation, reward, done) = env.step(&actions);
+                Response::Step {
+                    observation,
+                    reward,
+                    done,
+                }
+            }
+            Err(e) => Response::Error {
+                error: e.to_string(),
+            },
+        };
+        writeln!(output, "{}", serde_json::to_string(&response)?)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOOT: Input = Input {
+        action: Actions::Shoot,
+        value: 1.0,
+    };
+
+    fn env(nearest: usize) -> Env {
+        Env::new(EnvConfig {
+            nearest,
+            ..default()
+        })
+        .expect("the game loads headless")
+    }
+
+    // enough steps for the spawner to bring some asteroids in
+    fn play(env: &mut Env, seed: u64) -> Vec<Observation> {
+        let mut observations = vec![env.reset(seed)];
+        for step in 0..120 {
+            let actions = if step % 2 == 0 { vec![SHOOT] } else { Vec::new() };
+            let (observation, _, done) = env.step(&actions);
+            observations.push(observation);
+            if done {
+                break;
+            }
+        }
+        observations
+    }
+
+    #[test]
+    fn same_seed_same_observations() {
+        let mut env = env(4);
+        let first = play(&mut env, 7);
+        let second = play(&mut env, 7);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn observation_layout() {
+        let nearest = 16;
+        let mut env = env(nearest);
+        let observations = play(&mut env, 3);
+        for observation in &observations {
+            assert_eq!(observation.len(), SHIP_FEATURES + nearest * ASTEROID_FEATURES);
+            assert_eq!(observation.len(), env.observation_size());
+        }
+
+        let last = observations.last().unwrap();
+        // sin and cos of the heading, then alive
+        assert!((last[4].powi(2) + last[5].powi(2) - 1.0).abs() < 1e-4);
+        assert_eq!(last[SHIP_FEATURES - 1], 1.0);
+
+        let asteroids: Vec<&[f32]> = last[SHIP_FEATURES..].chunks(ASTEROID_FEATURES).collect();
+        let present = asteroids.iter().take_while(|asteroid| asteroid[5] == 1.0).count();
+        assert!(present > 0, "nothing spawned to observe");
+        // closest first, then zeros once they run out
+        let distances: Vec<f32> = asteroids[..present]
+            .iter()
+            .map(|asteroid| Vec2::new(asteroid[0], asteroid[1]).length())
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(asteroids[..present].iter().all(|asteroid| asteroid[4] > 0.0));
+        assert!(asteroids[present..]
+            .iter()
+            .all(|asteroid| asteroid.iter().all(|feature| *feature == 0.0)));
+    }
+}
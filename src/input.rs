@@ -1,27 +1,89 @@
-use crate::control::PlayerController;
+use crate::bodies::heading;
+use crate::console::{ConfigExt, ConsoleExt};
+use crate::control::{Controller, PlayerController};
 use crate::schedule::InGameSet;
 use crate::input_actions::*;
+use crate::touch::{TouchControl, TouchState};
 use bevy::prelude::*;
 use std::{collections::HashMap, time::Duration};
 use serde::{Deserialize, Serialize};
-use bevy_common_assets::ron::RonAssetPlugin;
 use bevy::input::gamepad::{Gamepad, GamepadButton};
 
 pub struct InputPlugin;
 
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RonAssetPlugin::<InputConfig>::new(&["input.ron"]));
+        app.add_config::<InputConfig>(&["input.ron"]);
         app.insert_resource(LoadInput(true));
+        app.init_resource::<ControlScheme>();
         app.add_event::<InputEvent>();
-        app.add_systems(Update, (handle_player_input,).in_set(InGameSet::GameInput));
+        app.add_systems(
+            Update,
+            (bind_gamepads, handle_player_input)
+                .chain()
+                .in_set(InGameSet::GameInput),
+        );
         app.add_systems(Update, (build_input_map_when_loaded)
             .run_if(load_input)
             .in_set(InGameSet::LoadEntities));
         app.add_systems(Startup, setup);
+        app.add_console_command("scheme", "scheme <classic|twin> [player], how a gamepad flies", scheme_command);
     }
 }
 
+// how a player's gamepad sticks fly the ship, the resource is what players start with
+#[derive(Component, Resource, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ControlScheme {
+    // sticks go through the GamepadAxis bindings, turning is a rate
+    #[default]
+    Classic,
+    // the left stick points the ship and thrusts, the right stick aims and fires
+    TwinStick,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct StickResponse {
+    // below this the stick is at rest
+    dead_zone: f32,
+    // applied past the dead zone, above 1 gives finer control near the middle
+    curve: f32,
+}
+
+impl StickResponse {
+    fn apply(&self, stick: Vec2) -> Option<(Vec2, f32)> {
+        let length = stick.length();
+        if length <= self.dead_zone {
+            return None;
+        }
+        let amount = ((length - self.dead_zone) / (1.0 - self.dead_zone).max(f32::EPSILON)).min(1.0);
+        Some((stick / length, amount.powf(self.curve)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct TwinStickConfig {
+    // shapes the thrust
+    left: StickResponse,
+    // the right stick only aims, past this it fires
+    right_dead_zone: f32,
+}
+
+impl Default for TwinStickConfig {
+    fn default() -> Self {
+        Self {
+            left: StickResponse {
+                dead_zone: 0.2,
+                curve: 1.5,
+            },
+            right_dead_zone: 0.3,
+        }
+    }
+}
+
+// the pad a local player flies with, handed out in player order as pads connect
+#[derive(Component, Debug, Clone, Copy)]
+pub struct BoundGamepad(pub Entity);
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -33,6 +95,8 @@ fn setup(
 #[derive(Debug, Clone, Serialize, Deserialize, Asset, TypePath)]
 struct InputConfig {
     bindings: Vec<Binding>,
+    #[serde(default)]
+    twin_stick: TwinStickConfig,
 }
 
 // for tracking config load
@@ -53,6 +117,8 @@ enum InputBinding {
     MouseButton(MouseButton),
     GamepadButton(GamepadButton),
     GamepadAxis(GamepadAxis),
+    // the on screen controls, sticks scale the value like GamepadAxis
+    Touch(TouchControl),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,7 +131,7 @@ struct Binding {
 type ActionFn = Box<dyn Fn(Entity) -> InputEvent + Send + Sync>;
 
 #[derive(Resource)]
-struct InputMap(HashMap<InputBinding, ActionFn>);
+struct InputMap(HashMap<InputBinding, ActionFn>, TwinStickConfig);
 
 fn build_input_map_from_config(config: InputConfig) -> InputMap {
     let mut map: HashMap<InputBinding, ActionFn> = HashMap::new();
@@ -76,7 +142,7 @@ fn build_input_map_from_config(config: InputConfig) -> InputMap {
             input: b.input,
         }));
     }
-    InputMap(map)
+    InputMap(map, config.twin_stick)
 }
 
 fn build_input_map_when_loaded(
@@ -94,11 +160,43 @@ fn build_input_map_when_loaded(
     }
 }
 
+// players without a connected pad get the first one nobody else has
+fn bind_gamepads(
+    mut commands: Commands,
+    controllers: Query<(Entity, &PlayerController, Option<&BoundGamepad>)>,
+    gamepads: Query<Entity, With<Gamepad>>,
+) {
+    let connected = |bound: Option<&BoundGamepad>| bound.is_some_and(|bound| gamepads.contains(bound.0));
+    let taken: Vec<Entity> = controllers
+        .iter()
+        .filter(|(_, _, bound)| connected(*bound))
+        .filter_map(|(_, _, bound)| bound.map(|bound| bound.0))
+        .collect();
+    let mut waiting: Vec<(u32, Entity)> = controllers
+        .iter()
+        .filter(|(_, _, bound)| !connected(*bound))
+        .map(|(entity, controller, _)| (*controller.get_id(), entity))
+        .collect();
+    waiting.sort();
+    let mut free = gamepads.iter().filter(|gamepad| !taken.contains(gamepad));
+    for (_, controller) in waiting {
+        let Some(gamepad) = free.next() else {
+            break;
+        };
+        commands.entity(controller).insert(BoundGamepad(gamepad));
+    }
+}
+
+type LocalPlayer<'a> = (Entity, Option<&'a ControlScheme>, Option<&'a BoundGamepad>);
+
+#[allow(clippy::too_many_arguments)]
 fn handle_player_input(
-    controllers: Query<Entity, With<PlayerController>>,
+    controllers: Query<LocalPlayer, With<PlayerController>>,
+    scheme: Res<ControlScheme>,
     keyboard: Res<ButtonInput<KeyCode>>,
     gamepads: Query<&Gamepad>,
     mouse: Res<ButtonInput<MouseButton>>,
+    touch: Res<TouchState>,
     input_map: Option<Res<InputMap>>,
     mut writer: EventWriter<InputEvent>,
 ) {
@@ -107,7 +205,13 @@ fn handle_player_input(
         None => return,
     };
 
-    for entity in controllers.iter() {
+    for (entity, controller_scheme, bound) in controllers.iter() {
+        let twin_stick = *controller_scheme.unwrap_or(&scheme) == ControlScheme::TwinStick;
+        // only the player's own pad, two players never fly each other's ships
+        let gamepad = bound.and_then(|bound| gamepads.get(bound.0).ok());
+        if let (true, Some(gamepad)) = (twin_stick, gamepad) {
+            twin_stick_input(entity, &input_map.1, gamepad, &mut writer);
+        }
         for (binding, action_fn) in &input_map.0 {
             match *binding {
                 InputBinding::KeyboardPressed(code) if keyboard.pressed(code) => {
@@ -116,24 +220,28 @@ fn handle_player_input(
                 InputBinding::KeyboardJustPressed(code) if keyboard.just_pressed(code) => {
                     writer.send(action_fn(entity));
                 }
-                InputBinding::GamepadButton(code) => {
-                    for gamepad in gamepads.iter() {
-                        if gamepad.pressed(code) {
-                            writer.send(action_fn(entity));
-                            break;
-                        }
-                    }
+                InputBinding::GamepadButton(code)
+                    if gamepad.is_some_and(|gamepad| gamepad.pressed(code)) =>
+                {
+                    writer.send(action_fn(entity));
                 }
                 InputBinding::MouseButton(code) if mouse.pressed(code) => {
                     writer.send(action_fn(entity));
                 }
+                // the sticks belong to the twin stick scheme
+                InputBinding::GamepadAxis(_) if twin_stick => {}
                 InputBinding::GamepadAxis(axis) => {
-                    for gamepad in gamepads.iter() {
-                        if let Some(axis_value) = gamepad.get(axis) {
-                            let mut event = action_fn(entity);
-                            event.input.value = axis_value * event.input.value;
-                            writer.send(event);
-                        }
+                    if let Some(axis_value) = gamepad.and_then(|gamepad| gamepad.get(axis)) {
+                        let mut event = action_fn(entity);
+                        event.input.value = axis_value * event.input.value;
+                        writer.send(event);
+                    }
+                }
+                InputBinding::Touch(control) => {
+                    if let Some(value) = touch.value(control) {
+                        let mut event = action_fn(entity);
+                        event.input.value *= value;
+                        writer.send(event);
                     }
                 }
                 _ => {}
@@ -141,3 +249,45 @@ fn handle_player_input(
         }
     }
 }
+
+fn twin_stick_input(
+    controller: Entity,
+    config: &TwinStickConfig,
+    gamepad: &Gamepad,
+    writer: &mut EventWriter<InputEvent>,
+) {
+    let send = |writer: &mut EventWriter<InputEvent>, action, value| {
+        writer.send(InputEvent {
+            controller,
+            input: Input { action, value },
+        });
+    };
+    if let Some((direction, amount)) = config.left.apply(gamepad.left_stick()) {
+        send(writer, Actions::Heading, heading(direction));
+        send(writer, Actions::MoveForward, amount);
+    }
+    let aim = gamepad.right_stick();
+    if aim.length() > config.right_dead_zone {
+        send(writer, Actions::Aim, heading(aim));
+    }
+}
+
+fn scheme_command(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    controllers: Query<(Entity, &PlayerController)>,
+) -> String {
+    let scheme = match args.first().map(|scheme| scheme.as_str()) {
+        Some("classic") => ControlScheme::Classic,
+        Some("twin") => ControlScheme::TwinStick,
+        _ => return "scheme <classic|twin> [player]".to_string(),
+    };
+    let id = args.get(1).and_then(|id| id.parse::<u32>().ok()).unwrap_or(0);
+    match controllers.iter().find(|(_, controller)| *controller.get_id() == id) {
+        Some((entity, _)) => {
+            commands.entity(entity).insert(scheme);
+            format!("player {} flies {:?}", id, scheme)
+        }
+        None => format!("no local player {}", id),
+    }
+}
@@ -6,13 +6,24 @@ pub enum Actions {
     MoveForward,
     Shoot,
     Rotate,
+    Shield,
+    // twin stick, values are world angles like Rotation
+    Heading,
+    Aim,
     Pause,
     Restart,
     Confirm,
     Cancel,
+    Save,
+    Load,
+    ToggleDebug,
+    ToggleMods,
+    ToggleAchievements,
+    MenuPrevious,
+    MenuNext,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Input {
     pub action: Actions,
     pub value: f32,
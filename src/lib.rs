@@ -1,45 +1,161 @@
+use crate::achievements::*;
+use crate::ai::*;
 use crate::asteroid::*;
+use crate::audio::*;
 use crate::bodies::*;
 use crate::bullet::*;
+use crate::camera::*;
+use crate::console::*;
+use crate::debug::*;
+use crate::gravity::*;
 use crate::grid::*;
 use crate::input::*;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mods::*;
+use crate::particles::*;
+use crate::powerup::*;
+use crate::save::*;
 use crate::score::*;
+use crate::shield::*;
 use crate::ship::*;
 use crate::spawner::*;
+use crate::stats::*;
 use crate::states::*;
+use crate::touch::*;
 use crate::schedule::SchudulePlugin;
+use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
+use bevy::render::settings::{RenderCreation, WgpuSettings};
+use bevy::render::RenderPlugin;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
 
 use bevy_turborand::prelude::*;
+use std::str::FromStr;
 
+pub use crate::ai::AutoplayPlugin;
+pub use crate::client::{ClientPlugin, ClientSettings};
+pub use crate::daily::{DailyPlugin, Day};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::gym::{serve, Env, EnvConfig, Observation};
+pub use crate::input::ControlScheme;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::mods::add_mod_packs;
+pub use crate::netplay::{NetplayPlugin, NetplaySettings};
+pub use crate::replay::{RecordPlugin, Replay, ReplayPlugin};
+pub use crate::replication::TICK_RATE;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::server::{ServerPlugin, ServerSettings};
+pub use crate::settings::{ScreenMode, Seed, Settings};
+pub use crate::ship_select::ShipSelectPlugin;
+
+mod achievements;
+mod ai;
 mod asteroid;
+mod audio;
 mod bodies;
 mod bullet;
+mod camera;
+mod client;
+mod console;
 mod control;
+mod daily;
+mod debug;
 mod input_actions;
+mod gravity;
 mod grid;
+#[cfg(not(target_arch = "wasm32"))]
+mod gym;
 mod input;
+#[cfg(not(target_arch = "wasm32"))]
+mod mods;
+mod netplay;
+mod particles;
+mod powerup;
+mod replay;
+mod replication;
+mod save;
 mod schedule;
 mod score;
+#[cfg(not(target_arch = "wasm32"))]
+mod server;
+mod settings;
+mod shield;
 mod ship;
+mod ship_select;
 mod spawner;
+mod stats;
 mod states;
+mod touch;
 
 const WORLD_SEED: u64 = 1024;
 
+// insert before AsteroidsGamePlugin to play a different world, WORLD_SEED otherwise
+#[derive(Resource, Clone, Copy)]
+pub struct WorldSeed(pub u64);
+
+// no window and nothing drawn, for the server and the gym
+// meshes and materials still load, there's just nothing to draw them with
+pub fn headless_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            ..default()
+        })
+        .set(RenderPlugin {
+            render_creation: RenderCreation::Automatic(WgpuSettings {
+                backends: None,
+                ..default()
+            }),
+            ..default()
+        })
+        .disable::<WinitPlugin>()
+}
+
+// for the binaries' flags, a typo falls back to the default rather than ending the game
+// reported with warn!, so parse after the log plugin is in
+pub fn parse_or<T: FromStr>(value: Option<&String>, usage: &str, default: T) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(parsed)) => parsed,
+        Some(Err(_)) => {
+            warn!("{}, using the default", usage);
+            default
+        }
+        None => default,
+    }
+}
+
 pub struct AsteroidsGamePlugin;
 
 impl Plugin for AsteroidsGamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RngPlugin::new().with_rng_seed(WORLD_SEED));
+        let seed = app.world().get_resource::<WorldSeed>().map_or(WORLD_SEED, |seed| seed.0);
+        app.insert_resource(WorldSeed(seed));
+        app.add_plugins(RngPlugin::new().with_rng_seed(seed));
         app.add_plugins(ScorePlugin);
+        app.add_plugins(StatsPlugin);
+        app.add_plugins(AchievementsPlugin);
         app.add_plugins(SchudulePlugin);
         app.add_plugins(ShipPlugin);
+        app.add_plugins(ShieldPlugin);
         app.add_plugins(BodiesPlugin);
+        app.add_plugins(GravityPlugin);
         app.add_plugins(StatePlugin);
         app.add_plugins(GridPlugin);
         app.add_plugins(BulletPlugin);
         app.add_plugins(AsteroidsPlugin);
+        app.add_plugins(PowerupPlugin);
         app.add_plugins(InputPlugin);
+        app.add_plugins(TouchPlugin);
+        app.add_plugins(ParticlesPlugin);
+        app.add_plugins(SoundPlugin);
+        app.add_plugins(CameraPlugin);
+        app.add_plugins(SavePlugin);
+        app.add_plugins(AiPlugin);
+        app.add_plugins(DebugPlugin);
+        app.add_plugins(ConsolePlugin);
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugins(ModsPlugin);
     }
 }
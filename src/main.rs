@@ -1,16 +1,158 @@
-use asteroids::AsteroidsGamePlugin;
+use asteroids::{
+    headless_plugins, parse_or, AsteroidsGamePlugin, AutoplayPlugin, ClientPlugin,
+    ClientSettings, DailyPlugin, Day, NetplayPlugin, NetplaySettings, RecordPlugin, Replay, ReplayPlugin, Settings,
+    ShipSelectPlugin, WorldSeed,
+};
+use bevy::app::ScheduleRunnerPlugin;
 use bevy::prelude::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// netplay is on when a peer is given, e.g. for two windows on one machine
+//   asteroids --local 127.0.0.1:7000 --peer 127.0.0.1:7001 --player 0
+//   asteroids --local 127.0.0.1:7001 --peer 127.0.0.1:7000 --player 1
+fn netplay_settings() -> Option<NetplaySettings> {
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+    };
+
+    // there's no guessing who to play against
+    let peer = value("--peer")?;
+    let Ok(peer) = peer.parse() else {
+        error!("--peer takes an address like 127.0.0.1:7001, not {}", peer);
+        std::process::exit(2);
+    };
+    let local = parse_or(
+        value("--local"),
+        "--local takes an address like 127.0.0.1:7000",
+        SocketAddr::from(([0, 0, 0, 0], 7000)),
+    );
+    let player = parse_or(value("--player"), "--player takes 0 or 1", 0);
+    let input_delay = parse_or(value("--delay"), "--delay takes a frame count", 2);
+
+    Some(NetplaySettings {
+        local,
+        peer,
+        player,
+        input_delay,
+    })
+}
+
+// joining a server, e.g. one started with the server binary
+//   asteroids --connect ws://127.0.0.1:7100 [--spectate]
+#[cfg(not(target_arch = "wasm32"))]
+fn client_settings() -> Option<ClientSettings> {
+    let args: Vec<String> = std::env::args().collect();
+    let server = args
+        .iter()
+        .position(|arg| arg == "--connect")
+        .and_then(|index| args.get(index + 1))?;
+    Some(ClientSettings {
+        server: server.clone(),
+        spectate: args.iter().any(|arg| arg == "--spectate"),
+    })
+}
+
+// the browser build takes the same from the page address, e.g. index.html?connect=ws://host:7100&spectate
+#[cfg(target_arch = "wasm32")]
+fn client_settings() -> Option<ClientSettings> {
+    let search = web_sys::window()?.location().search().ok()?;
+    let query = search.trim_start_matches('?');
+    let server = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("connect="))?;
+    Some(ClientSettings {
+        server: server.to_string(),
+        spectate: query.split('&').any(|pair| pair == "spectate"),
+    })
+}
+
+// the game plays itself, skill indexes the levels in a.ai.ron
+//   asteroids --autoplay 2
+fn autoplay_skill() -> Option<usize> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == "--autoplay")?;
+    Some(args.get(index + 1).and_then(|skill| skill.parse().ok()).unwrap_or(1))
+}
+
+// today's challenge, the same world and ship for everyone
+//   asteroids --daily
+#[cfg(not(target_arch = "wasm32"))]
+fn daily_challenge() -> Option<Day> {
+    std::env::args().any(|arg| arg == "--daily").then(Day::today)
+}
+
+// index.html?daily
+#[cfg(target_arch = "wasm32")]
+fn daily_challenge() -> Option<Day> {
+    let search = web_sys::window()?.location().search().ok()?;
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .any(|pair| pair == "daily")
+        .then(Day::today)
+}
 
 // test workflow
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                fit_canvas_to_parent: true,
-                ..default()
-            }),
+    let settings = Settings::load();
+    let daily = daily_challenge();
+
+    let mut app = App::new();
+    // browsers only get the stock assets, and so does the daily so every run is under the same rules
+    #[cfg(not(target_arch = "wasm32"))]
+    if daily.is_none() {
+        asteroids::add_mod_packs(&mut app, settings.assets.as_deref());
+    }
+    if settings.headless {
+        app.add_plugins(headless_plugins())
+            .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f32(1.0 / 60.0)));
+    } else {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(settings.window()),
             ..default()
-        }))
-        .add_plugins(AsteroidsGamePlugin)
-        .run();
+        }));
+    }
+    settings.report();
+    // a replay that won't load is reported and the game starts as it would without one
+    let replay = settings.replay.as_ref().and_then(|path| match Replay::load(path) {
+        Ok(replay) => Some(replay),
+        Err(e) => {
+            error!("--replay {}: {}, starting a normal game", path.display(), e);
+            None
+        }
+    });
+    // a replay only plays back on the world it was recorded in
+    let seed = match (&replay, daily) {
+        (Some(replay), _) => replay.seed,
+        (None, Some(day)) => day.seed(),
+        (None, None) => settings.world_seed(),
+    };
+    app.insert_resource(WorldSeed(seed))
+        .insert_resource(settings.scheme)
+        .add_plugins(AsteroidsGamePlugin);
+
+    if let Some(replay) = replay {
+        if replay.ship_select {
+            app.add_plugins(ShipSelectPlugin);
+        }
+        app.add_plugins(ReplayPlugin { replay });
+    } else if let Some(day) = daily {
+        app.add_plugins(DailyPlugin { day });
+    } else if let Some(settings) = client_settings() {
+        app.add_plugins(ClientPlugin { settings });
+    } else if let Some(settings) = netplay_settings() {
+        app.add_plugins(NetplayPlugin { settings });
+    } else if let Some(skill) = autoplay_skill() {
+        app.add_plugins(AutoplayPlugin { skill });
+    } else {
+        app.add_plugins(ShipSelectPlugin);
+    }
+    if let Some(path) = settings.record {
+        app.add_plugins(RecordPlugin { path });
+    }
+    app.run();
 }
@@ -0,0 +1,708 @@
+use crate::input_actions::*;
+use crate::schedule::InGameSet;
+use bevy::asset::io::{
+    AssetReader, AssetReaderError, AssetSource, AssetSourceId, ErasedAssetReader, PathStream,
+    Reader, VecReader,
+};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// packs live here as directories or zips, each with a mod.ron at the top
+const MODS_DIR: &str = "mods";
+// which packs are on, highest priority first, written by the mods screen
+const MOD_LIST_PATH: &str = "mods.ron";
+const MANIFEST: &str = "mod.ron";
+// the same place AssetPlugin reads from by default
+const BASE_ASSETS: &str = "assets";
+// bump when a change to the configs would break existing packs
+const MOD_FORMAT: u32 = 1;
+
+// a pack overrides any file under assets by shipping one at the same path,
+// so new asteroid kinds come with their own a.ast.ron and any meshes it points at
+// files are replaced whole, never merged: a pack adding a kind or a ship carries the stock
+// entries too, and of two packs that both do, only the higher one's list is used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    // the MOD_FORMAT it was written against
+    pub format: u32,
+    // the oldest game it works with
+    pub game_version: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ModList {
+    enabled: Vec<String>,
+}
+
+#[derive(Clone)]
+enum Source {
+    Dir(PathBuf),
+    Zip(Arc<ZipPack>),
+}
+
+pub struct FoundMod {
+    // the directory or zip name without the extension
+    pub id: String,
+    pub manifest: Option<ModManifest>,
+    // why it can't be used, if it can't
+    pub problem: Option<String>,
+    source: Option<Source>,
+}
+
+// what's in the mods directory and what's switched on, changes only apply on the next run
+#[derive(Resource)]
+pub struct ModPacks {
+    pub found: Vec<FoundMod>,
+    pub enabled: Vec<String>,
+    pub active: Vec<String>,
+}
+
+// just enough of the zip format for packs, stored or deflated, no zip64
+struct ZipPack {
+    bytes: Vec<u8>,
+    entries: HashMap<PathBuf, ZipEntry>,
+}
+
+struct ZipEntry {
+    method: u16,
+    offset: usize,
+    compressed: usize,
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+impl ZipPack {
+    fn open(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let entries = Self::entries(&bytes).ok_or("not a zip this game can read")?;
+        Ok(Self { bytes, entries })
+    }
+
+    fn entries(bytes: &[u8]) -> Option<HashMap<PathBuf, ZipEntry>> {
+        // the end of central directory record, found from the back past any comment
+        let end = (0..bytes.len().saturating_sub(21))
+            .rev()
+            .find(|at| u32_at(bytes, *at) == Some(0x0605_4b50))?;
+        let count = u16_at(bytes, end + 10)? as usize;
+        let mut at = u32_at(bytes, end + 16)? as usize;
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            if u32_at(bytes, at)? != 0x0201_4b50 {
+                return None;
+            }
+            let method = u16_at(bytes, at + 10)?;
+            let compressed = u32_at(bytes, at + 20)? as usize;
+            let name_length = u16_at(bytes, at + 28)? as usize;
+            let extra_length = u16_at(bytes, at + 30)? as usize;
+            let comment_length = u16_at(bytes, at + 32)? as usize;
+            let header = u32_at(bytes, at + 42)? as usize;
+            let name = std::str::from_utf8(bytes.get(at + 46..at + 46 + name_length)?).ok()?;
+            at += 46 + name_length + extra_length + comment_length;
+
+            if name.ends_with('/') {
+                continue;
+            }
+            // the local header repeats the name and may carry a different extra field
+            let offset = header
+                + 30
+                + u16_at(bytes, header + 26)? as usize
+                + u16_at(bytes, header + 28)? as usize;
+            entries.insert(
+                PathBuf::from(name),
+                ZipEntry {
+                    method,
+                    offset,
+                    compressed,
+                },
+            );
+        }
+
+        // zipping a folder puts everything one level down, look inside it
+        if !entries.contains_key(Path::new(MANIFEST)) {
+            let manifests: Vec<&PathBuf> = entries
+                .keys()
+                .filter(|name| {
+                    name.file_name() == Some(MANIFEST.as_ref()) && name.components().count() == 2
+                })
+                .collect();
+            if let [manifest] = manifests[..] {
+                let folder = manifest.parent()?.to_path_buf();
+                entries = entries
+                    .into_iter()
+                    .filter_map(|(name, entry)| {
+                        Some((name.strip_prefix(&folder).ok()?.to_path_buf(), entry))
+                    })
+                    .collect();
+            }
+        }
+        Some(entries)
+    }
+
+    fn read(&self, path: &Path) -> Option<Result<Vec<u8>, String>> {
+        let entry = self.entries.get(path)?;
+        let Some(data) = self
+            .bytes
+            .get(entry.offset..entry.offset + entry.compressed)
+        else {
+            return Some(Err(format!(
+                "{} runs past the end of the zip",
+                path.display()
+            )));
+        };
+        Some(match entry.method {
+            0 => Ok(data.to_vec()),
+            8 => miniz_oxide::inflate::decompress_to_vec(data)
+                .map_err(|e| format!("{}: {:?}", path.display(), e)),
+            method => Err(format!(
+                "{} uses zip method {}, only stored and deflate work",
+                path.display(),
+                method
+            )),
+        })
+    }
+}
+
+// serves each file from the first pack that has it, then from assets
+struct LayeredReader {
+    layers: Arc<Vec<Source>>,
+    base: Box<dyn ErasedAssetReader>,
+}
+
+impl LayeredReader {
+    async fn find<'a>(
+        &'a self,
+        path: &'a Path,
+        meta: bool,
+    ) -> Result<Box<dyn Reader + 'a>, AssetReaderError> {
+        let name = if meta {
+            meta_path(path)
+        } else {
+            path.to_path_buf()
+        };
+        for layer in self.layers.iter() {
+            match layer {
+                Source::Dir(dir) => {
+                    // packs are small, reading them outright is simpler than another async reader each
+                    if let Ok(bytes) = std::fs::read(dir.join(&name)) {
+                        return Ok(Box::new(VecReader::new(bytes)));
+                    }
+                }
+                Source::Zip(zip) => match zip.read(&name) {
+                    Some(Ok(bytes)) => return Ok(Box::new(VecReader::new(bytes))),
+                    Some(Err(e)) => {
+                        return Err(AssetReaderError::Io(Arc::new(std::io::Error::other(e))))
+                    }
+                    None => (),
+                },
+            }
+        }
+        if meta {
+            self.base.read_meta(path).await
+        } else {
+            self.base.read(path).await
+        }
+    }
+}
+
+// the same name bevy gives meta files, a.ship.ron.meta
+fn meta_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".meta");
+    PathBuf::from(name)
+}
+
+impl AssetReader for LayeredReader {
+    async fn read<'a>(&'a self, path: &'a Path) -> Result<impl Reader + 'a, AssetReaderError> {
+        self.find(path, false).await
+    }
+
+    async fn read_meta<'a>(&'a self, path: &'a Path) -> Result<impl Reader + 'a, AssetReaderError> {
+        self.find(path, true).await
+    }
+
+    // packs can replace files but not add to folders, nothing loads a whole folder
+    async fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Result<Box<PathStream>, AssetReaderError> {
+        self.base.read_directory(path).await
+    }
+
+    async fn is_directory<'a>(&'a self, path: &'a Path) -> Result<bool, AssetReaderError> {
+        self.base.is_directory(path).await
+    }
+}
+
+fn parse_version(text: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = text.trim().split('.').map(|part| part.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+fn check(manifest: &ModManifest) -> Option<String> {
+    if manifest.format != MOD_FORMAT {
+        return Some(format!(
+            "made for mod format {}, this game reads {}",
+            manifest.format, MOD_FORMAT
+        ));
+    }
+    match (
+        parse_version(&manifest.game_version),
+        parse_version(env!("CARGO_PKG_VERSION")),
+    ) {
+        (Some(needed), Some(running)) if needed > running => Some(format!(
+            "needs game version {} or later",
+            manifest.game_version
+        )),
+        (None, _) => Some(format!(
+            "game version {} isn't a version",
+            manifest.game_version
+        )),
+        _ => None,
+    }
+}
+
+fn read_manifest(source: &Source) -> Result<ModManifest, String> {
+    let text = match source {
+        Source::Dir(dir) => std::fs::read(dir.join(MANIFEST)).map_err(|e| e.to_string())?,
+        Source::Zip(zip) => zip.read(Path::new(MANIFEST)).ok_or("no mod.ron")??,
+    };
+    let text = String::from_utf8(text).map_err(|e| e.to_string())?;
+    ron::from_str(&text).map_err(|e| e.to_string())
+}
+
+fn scan() -> Vec<FoundMod> {
+    let Ok(entries) = std::fs::read_dir(MODS_DIR) else {
+        return Vec::new();
+    };
+    let mut found: Vec<FoundMod> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let id = path.file_stem()?.to_string_lossy().into_owned();
+            let source = if path.is_dir() {
+                Source::Dir(path)
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+            {
+                match ZipPack::open(&path) {
+                    Ok(zip) => Source::Zip(Arc::new(zip)),
+                    Err(e) => {
+                        return Some(FoundMod {
+                            id,
+                            manifest: None,
+                            problem: Some(e),
+                            source: None,
+                        })
+                    }
+                }
+            } else {
+                return None;
+            };
+            let (manifest, problem) = match read_manifest(&source) {
+                Ok(manifest) => {
+                    let problem = check(&manifest);
+                    (Some(manifest), problem)
+                }
+                Err(e) => (None, Some(format!("bad mod.ron, {}", e))),
+            };
+            Some(FoundMod {
+                id,
+                manifest,
+                problem,
+                source: Some(source),
+            })
+        })
+        .collect();
+    found.sort_by(|a, b| a.id.cmp(&b.id));
+    found
+}
+
+fn load_mod_list() -> ModList {
+    std::fs::read_to_string(MOD_LIST_PATH)
+        .ok()
+        .and_then(|text| ron::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_mod_list(enabled: &[String]) {
+    let list = ModList {
+        enabled: enabled.to_vec(),
+    };
+    let result = ron::ser::to_string_pretty(&list, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|text| std::fs::write(MOD_LIST_PATH, text).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("could not save {}: {}", MOD_LIST_PATH, e);
+    }
+}
+
+// has to happen before DefaultPlugins, the asset server only takes sources as it starts
+// an --assets directory sits above every mod
+pub fn add_mod_packs(app: &mut App, assets: Option<&Path>) {
+    let found = scan();
+    let enabled = load_mod_list().enabled;
+
+    let mut layers = Vec::new();
+    if let Some(dir) = assets {
+        layers.push(Source::Dir(dir.to_path_buf()));
+    }
+    let mut active = Vec::new();
+    // any that can't be used are logged by report_mods once logging is up
+    for id in enabled.iter() {
+        if let Some(FoundMod {
+            problem: None,
+            source: Some(source),
+            ..
+        }) = found.iter().find(|found| &found.id == id)
+        {
+            layers.push(source.clone());
+            active.push(id.clone());
+        }
+    }
+
+    if !layers.is_empty() {
+        let layers = Arc::new(layers);
+        let mut base = AssetSource::get_default_reader(BASE_ASSETS.to_string());
+        let source = AssetSource::build().with_reader(move || {
+            Box::new(LayeredReader {
+                layers: layers.clone(),
+                base: base(),
+            })
+        });
+        app.register_asset_source(AssetSourceId::Default, source);
+    }
+    app.insert_resource(ModPacks {
+        found,
+        enabled,
+        active,
+    });
+}
+
+fn report_mods(packs: Res<ModPacks>) {
+    for id in packs.enabled.iter().filter(|id| !packs.active.contains(id)) {
+        match packs.found.iter().find(|found| &found.id == id) {
+            Some(found) => warn!(
+                "mod {} is off, {}",
+                id,
+                found.problem.as_deref().unwrap_or_default()
+            ),
+            None => warn!("mod {} is enabled but isn't in {}", id, MODS_DIR),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ModsScreen {
+    open: bool,
+    selected: usize,
+}
+
+#[derive(Component)]
+struct ModsPanel;
+
+#[derive(Component)]
+struct ModsText;
+
+// enabled packs in priority order, then the rest
+fn listed(packs: &ModPacks) -> Vec<String> {
+    let mut ids = packs.enabled.clone();
+    ids.extend(
+        packs
+            .found
+            .iter()
+            .filter(|found| !packs.enabled.contains(&found.id))
+            .map(|found| found.id.clone()),
+    );
+    ids
+}
+
+fn toggle_screen(mut screen: ResMut<ModsScreen>, mut events: EventReader<InputEvent>) {
+    for event in events.read() {
+        if event.input.action == Actions::ToggleMods {
+            screen.open = !screen.open;
+        }
+    }
+}
+
+// the screen takes the keyboard while it's open, like the console
+fn use_screen(
+    mut screen: ResMut<ModsScreen>,
+    mut packs: ResMut<ModPacks>,
+    mut events: EventReader<KeyboardInput>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+) {
+    let was_open = screen.open;
+    for event in events.read() {
+        if !screen.open || !event.state.is_pressed() {
+            continue;
+        }
+        let ids = listed(&packs);
+        let Some(id) = ids.get(screen.selected).cloned() else {
+            if matches!(event.logical_key, Key::Escape | Key::F4) {
+                screen.open = false;
+            }
+            continue;
+        };
+        let position = packs.enabled.iter().position(|enabled| *enabled == id);
+        match (&event.logical_key, position) {
+            (Key::Escape | Key::F4, _) => screen.open = false,
+            (Key::ArrowUp, _) => screen.selected = screen.selected.saturating_sub(1),
+            (Key::ArrowDown, _) => screen.selected = (screen.selected + 1).min(ids.len() - 1),
+            (Key::Enter | Key::Space, Some(index)) => {
+                packs.enabled.remove(index);
+                // it drops to the top of the disabled ones
+                screen.selected = packs.enabled.len();
+                save_mod_list(&packs.enabled);
+            }
+            (Key::Enter | Key::Space, None) => {
+                packs.enabled.push(id);
+                screen.selected = packs.enabled.len() - 1;
+                save_mod_list(&packs.enabled);
+            }
+            (Key::PageUp, Some(index)) if index > 0 => {
+                packs.enabled.swap(index, index - 1);
+                screen.selected = index - 1;
+                save_mod_list(&packs.enabled);
+            }
+            (Key::PageDown, Some(index)) if index + 1 < packs.enabled.len() => {
+                packs.enabled.swap(index, index + 1);
+                screen.selected = index + 1;
+                save_mod_list(&packs.enabled);
+            }
+            _ => (),
+        }
+    }
+    if was_open || screen.open {
+        keyboard.reset_all();
+    }
+}
+
+fn show_screen(
+    mut commands: Commands,
+    screen: Res<ModsScreen>,
+    packs: Res<ModPacks>,
+    panels: Query<Entity, With<ModsPanel>>,
+    mut texts: Query<&mut Text, With<ModsText>>,
+) {
+    if !screen.is_changed() && !packs.is_changed() {
+        return;
+    }
+    if !screen.open {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+
+    let mut shown = String::from("Mods, highest priority first\n");
+    shown.push_str("up/down select  enter on/off  pgup/pgdn priority  F4 close\n\n");
+    let ids = listed(&packs);
+    if ids.is_empty() {
+        let _ = writeln!(
+            shown,
+            "nothing in {}/, packs are folders or zips with a {}",
+            MODS_DIR, MANIFEST
+        );
+    }
+    for (index, id) in ids.iter().enumerate() {
+        let cursor = if index == screen.selected { ">" } else { " " };
+        let tick = if packs.enabled.contains(id) { "x" } else { " " };
+        let found = packs.found.iter().find(|found| &found.id == id);
+        let _ = write!(shown, "{} [{}] ", cursor, tick);
+        match found.and_then(|found| found.manifest.as_ref()) {
+            Some(manifest) => {
+                let _ = write!(shown, "{} {}", manifest.name, manifest.version);
+                if !manifest.description.is_empty() {
+                    let _ = write!(shown, " - {}", manifest.description);
+                }
+            }
+            None => shown.push_str(id),
+        }
+        match found.map(|found| found.problem.as_deref()) {
+            Some(Some(problem)) => {
+                let _ = write!(shown, "  ({})", problem);
+            }
+            None => shown.push_str("  (missing)"),
+            Some(None) => (),
+        }
+        shown.push('\n');
+    }
+    if packs.enabled != packs.active {
+        shown.push_str("\nrestart to apply");
+    }
+
+    if let Ok(mut text) = texts.get_single_mut() {
+        text.0 = shown;
+        return;
+    }
+    commands
+        .spawn((
+            ModsPanel,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(15.0),
+                top: Val::Percent(15.0),
+                width: Val::Percent(70.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.85)),
+            GlobalZIndex(15),
+        ))
+        .with_child((
+            ModsText,
+            Text::new(shown),
+            TextFont {
+                font_size: 18.0,
+                ..default()
+            },
+        ));
+}
+
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ModsScreen>();
+        app.add_systems(Startup, report_mods.run_if(resource_exists::<ModPacks>));
+        // only the game binary looks for mods, the others play the stock game
+        app.add_systems(
+            Update,
+            toggle_screen
+                .in_set(InGameSet::GameInput)
+                .run_if(resource_exists::<ModPacks>),
+        );
+        app.add_systems(
+            PreUpdate,
+            use_screen
+                .after(InputSystem)
+                .run_if(resource_exists::<ModPacks>),
+        );
+        app.add_systems(Update, show_screen.run_if(resource_exists::<ModPacks>));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a zip as an archiver would write it, method 0 is stored and 8 deflated
+    fn fixture(files: &[(&str, u16, &[u8])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut central = Vec::new();
+        for (name, method, data) in files {
+            let header = bytes.len() as u32;
+            bytes.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            bytes.extend_from_slice(&[20, 0, 0, 0]);
+            bytes.extend_from_slice(&method.to_le_bytes());
+            // time, date and crc, none of which are read
+            bytes.extend_from_slice(&[0; 8]);
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(data);
+
+            central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 8]);
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            // extra, comment, disk, internal and external attributes
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&header.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let offset = bytes.len() as u32;
+        bytes.extend_from_slice(&central);
+        bytes.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes
+    }
+
+    fn pack(bytes: Vec<u8>) -> Option<ZipPack> {
+        let entries = ZipPack::entries(&bytes)?;
+        Some(ZipPack { bytes, entries })
+    }
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        let ship = b"ShipConfig(ships: [])".as_slice();
+        let deflated = miniz_oxide::deflate::compress_to_vec(ship, 6);
+        let zip = pack(fixture(&[
+            ("mod.ron", 0, b"manifest"),
+            ("meshes/", 0, b""),
+            ("a.ship.ron", 8, &deflated),
+        ]))
+        .unwrap();
+
+        // folders aren't entries
+        assert_eq!(zip.entries.len(), 2);
+        assert_eq!(zip.read(Path::new("mod.ron")), Some(Ok(b"manifest".to_vec())));
+        assert_eq!(zip.read(Path::new("a.ship.ron")), Some(Ok(ship.to_vec())));
+    }
+
+    #[test]
+    fn looks_inside_a_zipped_folder() {
+        let zip = pack(fixture(&[
+            ("hard/mod.ron", 0, b"manifest"),
+            ("hard/a.ast.ron", 0, b"rocks"),
+        ]))
+        .unwrap();
+        assert_eq!(zip.read(Path::new("a.ast.ron")), Some(Ok(b"rocks".to_vec())));
+    }
+
+    #[test]
+    fn missing_entry() {
+        let zip = pack(fixture(&[("mod.ron", 0, b"manifest")])).unwrap();
+        assert_eq!(zip.read(Path::new("a.ship.ron")), None);
+    }
+
+    #[test]
+    fn truncated_file() {
+        let bytes = fixture(&[("mod.ron", 0, b"manifest"), ("a.ast.ron", 0, b"rocks")]);
+        // the central directory is at the end, without it there's nothing to go on
+        for length in [0, 10, bytes.len() / 2, bytes.len() - 1] {
+            assert!(ZipPack::entries(&bytes[..length]).is_none(), "{}", length);
+        }
+
+        // an entry claiming more than is there
+        let mut zip = pack(bytes).unwrap();
+        zip.entries.get_mut(Path::new("a.ast.ron")).unwrap().compressed = 1000;
+        assert!(matches!(zip.read(Path::new("a.ast.ron")), Some(Err(_))));
+    }
+
+    #[test]
+    fn unknown_method() {
+        let zip = pack(fixture(&[("mod.ron", 12, b"bzip2")])).unwrap();
+        assert!(matches!(zip.read(Path::new("mod.ron")), Some(Err(_))));
+    }
+}
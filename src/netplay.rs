@@ -0,0 +1,589 @@
+use crate::asteroid::AsteroidAssets;
+use crate::bullet::BulletAssets;
+use crate::control::{NetController, PlayerController};
+use crate::grid::Grid;
+use crate::input_actions::*;
+use crate::save::{restore_snapshot, take_snapshot, Snapshot};
+use crate::schedule::{InGameSet, Resimulating};
+use crate::ship::{Pilot, ShipAsset};
+use crate::states::StatesLocked;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+// both peers step the simulation by exactly this much, whatever the display rate
+const FRAME: Duration = Duration::from_nanos(16_666_667);
+// how many frames we guess the peer's input for before waiting on it
+const MAX_PREDICTION: u32 = 8;
+// checksums are swapped this often to catch the peers drifting apart
+const CHECKSUM_INTERVAL: u32 = 30;
+// the only actions that steer the simulation, everything else stays local
+const SIMULATED: [Actions; 6] = [
+    Actions::MoveForward,
+    Actions::Rotate,
+    Actions::Shoot,
+    Actions::Shield,
+    Actions::Heading,
+    Actions::Aim,
+];
+
+#[derive(Resource, Clone, Debug)]
+pub struct NetplaySettings {
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+    // 0 or 1, picks which ship is ours
+    pub player: u32,
+    // frames between pressing a key and it acting, hides most of the round trip
+    pub input_delay: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Packet {
+    // bumped on every restart so stragglers from the last game are dropped
+    round: u32,
+    // every frame of the sender's input we haven't acknowledged yet, starting at first
+    first: u32,
+    inputs: Vec<Vec<Input>>,
+    // last frame of the receiver's input the sender has, so it can stop resending
+    ack: Option<u32>,
+    checksum: Option<(u32, u64)>,
+    ready: bool,
+    restart: bool,
+}
+
+// the simulation as it stood once a frame's despawns were done, see snapshot_frame
+struct FrameState {
+    snapshot: Snapshot,
+    game_over: bool,
+}
+
+#[derive(Resource)]
+struct Session {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    player: usize,
+    input_delay: u32,
+    capture: Entity,
+    pilots: [Entity; 2],
+    round: u32,
+    local_ready: bool,
+    remote_ready: bool,
+    started: bool,
+    // frame being simulated by the current update, and the one after it
+    frame: u32,
+    next_frame: u32,
+    running: bool,
+    // the first frame of a rollback starts from its snapshot, halfway through
+    restored: bool,
+    resimulating: bool,
+    accumulated: Duration,
+    local_inputs: BTreeMap<u32, Vec<Input>>,
+    remote_inputs: BTreeMap<u32, Vec<Input>>,
+    // what we went with for the peer on frames it hasn't sent yet
+    predicted: BTreeMap<u32, Vec<Input>>,
+    // every remote frame up to here has arrived
+    confirmed: Option<u32>,
+    peer_ack: Option<u32>,
+    rollback: Option<u32>,
+    frames: BTreeMap<u32, FrameState>,
+    last_checksum: Option<u32>,
+    outgoing_checksum: Option<(u32, u64)>,
+    local_checksums: BTreeMap<u32, u64>,
+    remote_checksums: BTreeMap<u32, u64>,
+    local_restart: bool,
+    remote_restart: bool,
+}
+
+impl Session {
+    // the first frame that could still be rolled back to
+    fn settled(&self) -> u32 {
+        self.confirmed.map_or(0, |frame| frame + 1)
+    }
+
+    fn reset(&mut self) {
+        self.frame = 0;
+        self.next_frame = 0;
+        self.running = false;
+        self.accumulated = Duration::ZERO;
+        self.local_inputs.clear();
+        self.remote_inputs.clear();
+        self.predicted.clear();
+        self.confirmed = None;
+        self.peer_ack = None;
+        self.rollback = None;
+        self.frames.clear();
+        self.last_checksum = None;
+        self.outgoing_checksum = None;
+        self.local_checksums.clear();
+        self.remote_checksums.clear();
+        self.local_restart = false;
+        self.remote_restart = false;
+        // nothing was pressed early enough to act on the first frames
+        for frame in 0..self.input_delay {
+            self.local_inputs.insert(frame, Vec::new());
+        }
+    }
+}
+
+// the clock each frame sees is fixed by its number, so a resimulated frame sees the same one
+fn frame_time(frame: u32) -> Time {
+    let mut time = Time::default();
+    time.advance_to(FRAME * frame);
+    time.advance_by(FRAME);
+    time
+}
+
+fn hash_of(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// summed per entity so the order they come back in after a restore doesn't matter
+fn checksum(snapshot: &Snapshot) -> u64 {
+    let mut sum = hash_of(&(
+        &snapshot.scores,
+        &snapshot.players,
+        snapshot.seed,
+        snapshot.spawn_timer,
+    ));
+    for entity in &snapshot.entities {
+        let text = ron::to_string(entity).unwrap_or_default();
+        sum = sum.wrapping_add(hash_of(&text));
+    }
+    sum
+}
+
+fn setup_session(mut commands: Commands, settings: Res<NetplaySettings>) {
+    // nothing to play without the socket, say why and stop rather than unwind
+    let socket = match UdpSocket::bind(settings.local).and_then(|socket| {
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }) {
+        Ok(socket) => socket,
+        Err(e) => {
+            error!("could not open the netplay socket on {}: {}", settings.local, e);
+            std::process::exit(1);
+        }
+    };
+
+    // the keyboard drives a controller with no ship, its inputs are sent rather than used
+    let capture = commands.spawn(PlayerController { id: settings.player }).id();
+    let pilots = [
+        commands
+            .spawn((
+                NetController { id: 0 },
+                Pilot {
+                    team: 1,
+                    spawn: Vec2::new(-2.0, 0.0),
+                    ..default()
+                },
+            ))
+            .id(),
+        commands
+            .spawn((
+                NetController { id: 1 },
+                Pilot {
+                    team: 2,
+                    spawn: Vec2::new(2.0, 0.0),
+                    ..default()
+                },
+            ))
+            .id(),
+    ];
+
+    let mut session = Session {
+        socket,
+        peer: settings.peer,
+        player: settings.player.min(1) as usize,
+        input_delay: settings.input_delay,
+        capture,
+        pilots,
+        round: 0,
+        local_ready: false,
+        remote_ready: false,
+        started: false,
+        frame: 0,
+        next_frame: 0,
+        running: false,
+        restored: false,
+        resimulating: false,
+        accumulated: Duration::ZERO,
+        local_inputs: BTreeMap::new(),
+        remote_inputs: BTreeMap::new(),
+        predicted: BTreeMap::new(),
+        confirmed: None,
+        peer_ack: None,
+        rollback: None,
+        frames: BTreeMap::new(),
+        last_checksum: None,
+        outgoing_checksum: None,
+        local_checksums: BTreeMap::new(),
+        remote_checksums: BTreeMap::new(),
+        local_restart: false,
+        remote_restart: false,
+    };
+    session.reset();
+    commands.insert_resource(session);
+}
+
+fn handle_packet(session: &mut Session, packet: Packet) {
+    if packet.round < session.round {
+        return;
+    }
+    if packet.round > session.round {
+        // the peer has already agreed to restart and moved on
+        session.remote_restart = true;
+        return;
+    }
+    session.remote_ready |= packet.ready;
+    session.remote_restart |= packet.restart;
+    session.peer_ack = session.peer_ack.max(packet.ack);
+    if let Some((frame, sum)) = packet.checksum {
+        session.remote_checksums.insert(frame, sum);
+    }
+
+    let settled = session.settled();
+    for (frame, inputs) in (packet.first..).zip(packet.inputs) {
+        if frame < settled || session.remote_inputs.contains_key(&frame) {
+            continue;
+        }
+        if let Some(guess) = session.predicted.remove(&frame) {
+            if guess != inputs {
+                session.rollback = Some(session.rollback.map_or(frame, |from| from.min(frame)));
+            }
+        }
+        session.remote_inputs.insert(frame, inputs);
+    }
+    while session.remote_inputs.contains_key(&session.settled()) {
+        session.confirmed = Some(session.settled());
+    }
+}
+
+fn receive_packets(session: &mut Session) {
+    let mut buffer = [0u8; 65507];
+    loop {
+        match session.socket.recv_from(&mut buffer) {
+            Ok((len, from)) if from == session.peer => {
+                let packet = std::str::from_utf8(&buffer[..len])
+                    .ok()
+                    .and_then(|text| ron::from_str::<Packet>(text).ok());
+                match packet {
+                    Some(packet) => handle_packet(session, packet),
+                    None => warn!("dropped a malformed netplay packet"),
+                }
+            }
+            Ok(_) => continue,
+            // a peer that isn't listening yet shows up as a refused send
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => continue,
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("netplay receive failed: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+// a restart only happens once both players have asked for it
+fn agree_restart(world: &mut World) {
+    let mut session = world.resource_mut::<Session>();
+    if session.local_restart && session.remote_restart {
+        session.round += 1;
+        session.reset();
+        world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::InGame);
+    }
+}
+
+// nothing may load mid game, one peer would see it a frame before the other
+pub fn ready(world: &World) -> bool {
+    world.contains_resource::<Grid>()
+        && world.contains_resource::<ShipAsset>()
+        && world.contains_resource::<AsteroidAssets>()
+        && world.contains_resource::<BulletAssets>()
+}
+
+fn resimulate(world: &mut World, from: u32) {
+    let (snapshot, game_over, end) = {
+        let session = world.resource::<Session>();
+        let Some(state) = session.frames.get(&from) else {
+            warn!("netplay cannot roll back to frame {}, too old", from);
+            return;
+        };
+        (state.snapshot.clone(), state.game_over, session.next_frame)
+    };
+
+    world.insert_resource(Resimulating);
+    // the frames being redone may have ended the game, it's back on until they say otherwise
+    if *world.resource::<State<GameState>>().get() == GameState::GameOver {
+        world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::InGame);
+        world.run_schedule(StateTransition);
+    }
+
+    *world.resource_mut::<Time>() = frame_time(from);
+    restore_snapshot(world, &snapshot);
+    let mut next_state = world.resource_mut::<NextState<GameState>>();
+    if game_over {
+        next_state.set(GameState::GameOver);
+    } else {
+        next_state.reset();
+    }
+
+    for frame in from..end {
+        *world.resource_mut::<Time>() = frame_time(frame);
+        let mut session = world.resource_mut::<Session>();
+        session.frame = frame;
+        session.running = true;
+        session.resimulating = true;
+        session.restored = frame == from;
+        world.run_schedule(Update);
+    }
+
+    let mut session = world.resource_mut::<Session>();
+    session.resimulating = false;
+    session.restored = false;
+    world.remove_resource::<Resimulating>();
+}
+
+// checksum the frames nothing can change any more, then forget them
+fn settle_frames(session: &mut Session) {
+    let settled = session.settled();
+    let mut sums = Vec::new();
+    for (frame, state) in session.frames.range(..=settled) {
+        if frame % CHECKSUM_INTERVAL == 0 && session.last_checksum < Some(*frame) {
+            sums.push((*frame, checksum(&state.snapshot)));
+        }
+    }
+    for (frame, sum) in sums {
+        session.local_checksums.insert(frame, sum);
+        session.last_checksum = Some(frame);
+        session.outgoing_checksum = Some((frame, sum));
+    }
+
+    let compared: Vec<u32> = session
+        .remote_checksums
+        .keys()
+        .filter(|frame| session.local_checksums.contains_key(frame))
+        .copied()
+        .collect();
+    for frame in compared {
+        let remote = session.remote_checksums.remove(&frame);
+        let local = session.local_checksums.remove(&frame);
+        if remote != local {
+            error!("netplay desync at frame {}", frame);
+        }
+    }
+
+    // keep the last confirmed input around to predict from
+    let keep_remote = settled.saturating_sub(1);
+    session.frames = session.frames.split_off(&settled);
+    session.remote_inputs = session.remote_inputs.split_off(&keep_remote);
+    session.predicted = session.predicted.split_off(&settled);
+    let keep_local = settled.min(session.peer_ack.map_or(0, |frame| frame + 1));
+    session.local_inputs = session.local_inputs.split_off(&keep_local);
+}
+
+// runs before update and decides whether it simulates a frame, rolling back first if a guess was wrong
+fn advance_session(world: &mut World) {
+    receive_packets(&mut world.resource_mut::<Session>());
+
+    // a late input can take back the death that ended the game, so this comes before the state check
+    if let Some(from) = world.resource_mut::<Session>().rollback.take() {
+        resimulate(world, from);
+    }
+
+    if *world.resource::<State<GameState>>().get() != GameState::InGame {
+        world.resource_mut::<Session>().running = false;
+        agree_restart(world);
+        return;
+    }
+
+    if !world.resource::<Session>().started {
+        let ready = ready(world);
+        let mut session = world.resource_mut::<Session>();
+        session.local_ready = ready;
+        session.started = ready && session.remote_ready;
+        if !session.started {
+            session.running = false;
+            return;
+        }
+    }
+
+    settle_frames(&mut world.resource_mut::<Session>());
+
+    let real_delta = world.resource::<Time<Real>>().delta();
+    let mut session = world.resource_mut::<Session>();
+    session.accumulated = (session.accumulated + real_delta).min(FRAME * 4);
+    let frame = session.next_frame;
+    // too far ahead of the peer, wait for it rather than guess any further
+    let ahead = frame >= session.settled() + MAX_PREDICTION;
+    if session.accumulated < FRAME || ahead {
+        session.running = false;
+        return;
+    }
+    session.accumulated -= FRAME;
+    session.running = true;
+    session.frame = frame;
+    session.next_frame += 1;
+    *world.resource_mut::<Time>() = frame_time(frame);
+}
+
+// taken once despawns are done, the points they scored have been counted by then
+fn snapshot_frame(world: &mut World) {
+    let session = world.resource::<Session>();
+    if !session.running || session.restored {
+        return;
+    }
+    let frame = session.frame;
+
+    let snapshot = take_snapshot(world);
+    let game_over = matches!(
+        world.resource::<NextState<GameState>>(),
+        NextState::Pending(GameState::GameOver)
+    );
+    world
+        .resource_mut::<Session>()
+        .frames
+        .insert(frame, FrameState { snapshot, game_over });
+}
+
+fn capture_inputs(
+    mut session: ResMut<Session>,
+    state: Res<State<GameState>>,
+    mut events: EventReader<InputEvent>,
+) {
+    let mut inputs = Vec::new();
+    for event in events.read() {
+        if event.controller != session.capture {
+            continue;
+        }
+        if event.input.action == Actions::Restart && *state.get() == GameState::GameOver {
+            session.local_restart = true;
+        }
+        if SIMULATED.contains(&event.input.action) {
+            inputs.push(event.input);
+        }
+    }
+    if session.running && !session.resimulating {
+        let frame = session.frame + session.input_delay;
+        session.local_inputs.insert(frame, inputs);
+    }
+}
+
+fn emit_inputs(mut session: ResMut<Session>, mut writer: EventWriter<InputEvent>) {
+    if !session.running {
+        return;
+    }
+    let frame = session.frame;
+    let local = session.local_inputs.get(&frame).cloned().unwrap_or_default();
+    let remote = match session.remote_inputs.get(&frame) {
+        Some(inputs) => inputs.clone(),
+        None => {
+            // guess the peer is still doing whatever it did last
+            let guess = session
+                .remote_inputs
+                .range(..frame)
+                .next_back()
+                .map(|(_, inputs)| inputs.clone())
+                .unwrap_or_default();
+            session.predicted.insert(frame, guess.clone());
+            guess
+        }
+    };
+
+    let local_pilot = session.pilots[session.player];
+    let remote_pilot = session.pilots[1 - session.player];
+    for input in local {
+        writer.send(InputEvent {
+            controller: local_pilot,
+            input,
+        });
+    }
+    for input in remote {
+        writer.send(InputEvent {
+            controller: remote_pilot,
+            input,
+        });
+    }
+}
+
+fn send_packet(session: Res<Session>) {
+    if session.resimulating {
+        return;
+    }
+    let first = session.peer_ack.map_or(0, |frame| frame + 1);
+    let last = session.local_inputs.keys().next_back().copied();
+    let inputs = match last {
+        Some(last) if last >= first => (first..=last)
+            .map(|frame| session.local_inputs.get(&frame).cloned().unwrap_or_default())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let packet = Packet {
+        round: session.round,
+        first,
+        inputs,
+        ack: session.confirmed,
+        checksum: session.outgoing_checksum,
+        ready: session.local_ready,
+        restart: session.local_restart,
+    };
+    if let Ok(text) = ron::to_string(&packet) {
+        // udp, a lost packet is covered by the next one
+        let _ = session.socket.send_to(text.as_bytes(), session.peer);
+    }
+}
+
+fn simulating(session: Res<Session>) -> bool {
+    session.running
+}
+
+fn despawning(session: Res<Session>) -> bool {
+    session.running && !session.restored
+}
+
+pub struct NetplayPlugin {
+    pub settings: NetplaySettings,
+}
+
+impl Plugin for NetplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone());
+        app.insert_resource(StatesLocked);
+        app.add_systems(PreStartup, setup_session);
+        app.add_systems(PreUpdate, advance_session);
+        app.configure_sets(
+            Update,
+            (
+                InGameSet::UpdateEntities,
+                InGameSet::CollisionDetection,
+                InGameSet::CollisionReaction,
+            )
+                .run_if(simulating),
+        );
+        app.configure_sets(Update, InGameSet::DespawnEntities.run_if(despawning));
+        app.add_systems(
+            Update,
+            snapshot_frame
+                .after(InGameSet::DespawnEntities)
+                .before(InGameSet::GameInput),
+        );
+        app.add_systems(
+            Update,
+            (capture_inputs, emit_inputs, send_packet)
+                .chain()
+                .in_set(InGameSet::GameInput),
+        );
+    }
+}
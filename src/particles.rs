@@ -0,0 +1,409 @@
+use crate::asteroid::AsteroidDestroyed;
+use crate::bodies::*;
+use crate::console::ConfigExt;
+use crate::grid::{CameraFocus, Grid};
+use crate::schedule::{resimulating, FreshEvents, InGameSet};
+use crate::ship::{Ship, ShipDestroyed};
+use crate::GameState;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+
+// counts are per burst, except for thrust where count is particles per second
+#[derive(Deserialize, Clone, Copy)]
+struct EmitterConfig {
+    count: usize,
+    count_per_scale: f32,
+    #[serde(deserialize_with = "low_high")]
+    speed: (f32, f32),
+    spread: f32,
+    #[serde(deserialize_with = "low_high")]
+    lifetime: (u64, u64),
+    damping: f32,
+    spin: f32,
+    size: f32,
+    color: (f32, f32, f32),
+}
+
+// ranges can be written either way round, the rng wants the low end first
+fn low_high<'de, D, T>(deserializer: D) -> Result<(T, T), D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + PartialOrd,
+{
+    let (a, b) = <(T, T)>::deserialize(deserializer)?;
+    Ok(if b < a { (b, a) } else { (a, b) })
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct ParticleConfig {
+    pool_size: usize,
+    explosion: EmitterConfig,
+    thrust: EmitterConfig,
+    debris: EmitterConfig,
+}
+
+#[derive(Resource)]
+struct ParticleConfigHandle {
+    config: Handle<ParticleConfig>,
+}
+
+#[derive(Clone, Copy)]
+enum Emitter {
+    Explosion,
+    Thrust,
+    Debris,
+}
+
+impl Emitter {
+    fn config(self, config: &ParticleConfig) -> &EmitterConfig {
+        match self {
+            Emitter::Explosion => &config.explosion,
+            Emitter::Thrust => &config.thrust,
+            Emitter::Debris => &config.debris,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ParticleAssets {
+    dot: Handle<Mesh>,
+    line: Handle<Mesh>,
+    explosion: Handle<ColorMaterial>,
+    thrust: Handle<ColorMaterial>,
+    debris: Handle<ColorMaterial>,
+}
+
+impl ParticleAssets {
+    fn mesh(&self, emitter: Emitter) -> Handle<Mesh> {
+        match emitter {
+            Emitter::Debris => self.line.clone(),
+            _ => self.dot.clone(),
+        }
+    }
+
+    fn material(&self, emitter: Emitter) -> Handle<ColorMaterial> {
+        match emitter {
+            Emitter::Explosion => self.explosion.clone(),
+            Emitter::Thrust => self.thrust.clone(),
+            Emitter::Debris => self.debris.clone(),
+        }
+    }
+}
+
+// particles are never despawned, finished ones are hidden and handed back to the pool
+#[derive(Resource, Default)]
+struct ParticlePool {
+    free: Vec<Entity>,
+    spawned: usize,
+}
+
+#[derive(Resource)]
+struct ParticleRng(RngComponent);
+
+#[derive(Component)]
+pub struct Particle {
+    born: Duration,
+    lifetime: Duration,
+    size: f32,
+    active: bool,
+}
+
+#[derive(Bundle)]
+struct ParticleBundle {
+    particle: Particle,
+    position: Position,
+    velocity: Velocity,
+    damping: Damping,
+    rotation: Rotation,
+    angular_velocity: AngularVelocity,
+    scale: Scale,
+}
+
+impl ParticleBundle {
+    fn new(
+        position: Vec2,
+        velocity: Vec2,
+        rotation: f32,
+        angular_velocity: f32,
+        born: Duration,
+        lifetime: Duration,
+        config: &EmitterConfig,
+    ) -> Self {
+        Self {
+            particle: Particle {
+                born,
+                lifetime,
+                size: config.size,
+                active: true,
+            },
+            position: Position(position),
+            velocity: Velocity(velocity),
+            damping: Damping(config.damping),
+            rotation: Rotation(rotation),
+            angular_velocity: AngularVelocity(angular_velocity),
+            scale: Scale(config.size),
+        }
+    }
+}
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.particles.ron");
+    commands.insert_resource(ParticleConfigHandle { config });
+    commands.insert_resource(ParticleRng(RngComponent::new()));
+}
+
+fn load_particles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    configs: Res<Assets<ParticleConfig>>,
+    config_handle: Res<ParticleConfigHandle>,
+    particle_assets: Option<Res<ParticleAssets>>,
+) {
+    if particle_assets.is_some() {
+        return;
+    }
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        let color = |emitter: &EmitterConfig| Color::srgb(emitter.color.0, emitter.color.1, emitter.color.2);
+        commands.insert_resource(ParticleAssets {
+            dot: meshes.add(Circle::new(1.0)),
+            // debris is a line segment one unit long, scale sets the length
+            line: meshes.add(Rectangle::new(1.0, 0.12)),
+            explosion: materials.add(color(&config.explosion)),
+            thrust: materials.add(color(&config.thrust)),
+            debris: materials.add(color(&config.debris)),
+        });
+    }
+}
+
+// emit a single particle, reusing a pooled entity if there is one
+fn emit(
+    commands: &mut Commands,
+    pool: &mut ParticlePool,
+    assets: &ParticleAssets,
+    pool_size: usize,
+    emitter: Emitter,
+    bundle: ParticleBundle,
+) {
+    let entity = match pool.free.pop() {
+        Some(entity) => entity,
+        None if pool.spawned < pool_size => {
+            pool.spawned += 1;
+            commands.spawn(Transform::default()).id()
+        }
+        None => return,
+    };
+    commands.entity(entity).insert((
+        bundle,
+        Mesh2d(assets.mesh(emitter)),
+        MeshMaterial2d(assets.material(emitter)),
+        Visibility::Visible,
+    ));
+}
+
+// what every emitting system needs, so bursts can be thrown from anywhere
+#[derive(SystemParam)]
+struct Emitters<'w, 's> {
+    commands: Commands<'w, 's>,
+    pool: ResMut<'w, ParticlePool>,
+    rng: Res<'w, ParticleRng>,
+    assets: Option<Res<'w, ParticleAssets>>,
+    time: Res<'w, Time>,
+    configs: Res<'w, Assets<ParticleConfig>>,
+    config_handle: Res<'w, ParticleConfigHandle>,
+}
+
+impl Emitters<'_, '_> {
+    // nothing to emit until the config is in and the meshes are built
+    fn config(&self) -> Option<&ParticleConfig> {
+        self.assets.as_ref()?;
+        self.configs.get(self.config_handle.config.id())
+    }
+
+    // emit a spray of particles around a heading, the spread is the half angle of the cone in degrees
+    fn burst(
+        &mut self,
+        emitter: Emitter,
+        count: usize,
+        position: Vec2,
+        velocity: Vec2,
+        heading: f32,
+        radius: f32,
+    ) {
+        let (Some(config), Some(assets)) = (
+            self.configs.get(self.config_handle.config.id()),
+            self.assets.as_deref(),
+        ) else {
+            return;
+        };
+        let rng = &self.rng.0;
+        let time = self.time.elapsed();
+        let emitter_config = emitter.config(config);
+        for _ in 0..count {
+            let angle = heading + (rng.f32_normalized() * emitter_config.spread).to_radians();
+            let direction = Rot2::radians(angle) * Vec2::Y;
+            let speed = emitter_config.speed.0 + rng.f32() * (emitter_config.speed.1 - emitter_config.speed.0);
+            let lifetime = rng.u64(emitter_config.lifetime.0..=emitter_config.lifetime.1);
+            emit(
+                &mut self.commands,
+                &mut self.pool,
+                assets,
+                config.pool_size,
+                emitter,
+                ParticleBundle::new(
+                    position + direction * radius * rng.f32(),
+                    velocity + direction * speed,
+                    rng.f32() * std::f32::consts::TAU,
+                    rng.f32_normalized() * emitter_config.spin,
+                    time,
+                    Duration::from_millis(lifetime),
+                    emitter_config,
+                ),
+            );
+        }
+    }
+}
+
+fn explode_asteroids(mut emitters: Emitters, mut events: FreshEvents<AsteroidDestroyed>) {
+    let Some(explosion) = emitters.config().map(|config| config.explosion) else {
+        return;
+    };
+    for event in events.read() {
+        let count = explosion.count + (explosion.count_per_scale * event.scale) as usize;
+        emitters.burst(
+            Emitter::Explosion,
+            count,
+            event.position,
+            event.velocity * 0.5,
+            0.0,
+            // matches the asteroid rigid body radius
+            event.scale * 0.01,
+        );
+    }
+}
+
+fn break_ships(mut emitters: Emitters, mut events: FreshEvents<ShipDestroyed>) {
+    let Some((debris, explosion)) = emitters
+        .config()
+        .map(|config| (config.debris, config.explosion))
+    else {
+        return;
+    };
+    for event in events.read() {
+        let count = debris.count + (debris.count_per_scale * event.scale) as usize;
+        emitters.burst(
+            Emitter::Debris,
+            count,
+            event.position,
+            event.velocity,
+            event.rotation,
+            0.05,
+        );
+        let count = explosion.count + (explosion.count_per_scale * event.scale) as usize;
+        emitters.burst(
+            Emitter::Explosion,
+            count,
+            event.position,
+            event.velocity,
+            event.rotation,
+            0.05,
+        );
+    }
+}
+
+// acceleration is only set while MoveForward is held, so use it to drive the exhaust
+fn emit_thrust(
+    mut emitters: Emitters,
+    ships: Query<(&Position, &Velocity, &Rotation, &Acceleration, &RigidBody), With<Ship>>,
+    mut accumulator: Local<f32>,
+) {
+    let Some(thrust) = emitters.config().map(|config| config.thrust) else {
+        return;
+    };
+    *accumulator += thrust.count as f32 * emitters.time.delta_secs();
+    let count = *accumulator as usize;
+    *accumulator -= count as f32;
+
+    for (position, velocity, rotation, acceleration, body) in &ships {
+        if acceleration.0.y <= 0.0 {
+            continue;
+        }
+        // exhaust leaves the back of the ship
+        let heading = rotation.0 + std::f32::consts::PI;
+        let nozzle = position.0 + Rot2::radians(heading) * Vec2::new(0.0, body.radius);
+        emitters.burst(Emitter::Thrust, count, nozzle, velocity.0, heading, 0.0);
+    }
+}
+
+fn expire_particles(
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<(Entity, &mut Particle, &mut Scale, &mut Velocity, &mut Visibility)>,
+) {
+    let time_elapsed = time.elapsed();
+    for (entity, mut particle, mut scale, mut velocity, mut visibility) in particles.iter_mut() {
+        if !particle.active {
+            continue;
+        }
+        let age = time_elapsed.saturating_sub(particle.born);
+        if age >= particle.lifetime {
+            particle.active = false;
+            velocity.0 = Vec2::ZERO;
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+        } else {
+            // shrink out rather than pop
+            let remaining = 1.0 - age.as_secs_f32() / particle.lifetime.as_secs_f32();
+            scale.0 = particle.size * remaining;
+        }
+    }
+}
+
+// the body systems only run in game, so keep the debris of a dead ship drifting on the game over screen
+fn settle_particles(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    focus: Res<CameraFocus>,
+    mut particles: Query<(&Particle, &mut Position, &mut Velocity, &Damping, &Scale, &mut Transform)>,
+) {
+    for (particle, mut position, mut velocity, damping, scale, mut transform) in particles.iter_mut() {
+        if !particle.active {
+            continue;
+        }
+        position.0 += velocity.0 * time.delta_secs();
+        velocity.0 *= (-damping.0 * time.delta_secs()).exp();
+        transform.translation = grid.project(position.0, focus.0).extend(0.);
+        transform.scale = Vec3::splat(scale.0);
+    }
+}
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<ParticleConfig>(&["particles.ron"]);
+        app.init_resource::<ParticlePool>();
+        app.add_systems(Startup, load_config);
+        app.add_systems(Update, (load_particles).in_set(InGameSet::LoadEntities));
+        app.add_systems(
+            Update,
+            (expire_particles)
+                .run_if(not(resimulating))
+                .in_set(InGameSet::UpdateEntities),
+        );
+        app.add_systems(
+            Update,
+            (explode_asteroids, break_ships, emit_thrust.run_if(not(resimulating)))
+                .in_set(InGameSet::CollisionReaction),
+        );
+        app.add_systems(
+            Update,
+            (expire_particles, settle_particles)
+                .chain()
+                .run_if(in_state(GameState::GameOver)),
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use crate::asteroid::{AsteroidDestroyed, SmartBomb};
+use crate::bodies::*;
+use crate::console::{Cheated, ConfigExt, ConsoleExt};
+use crate::schedule::InGameSet;
+use crate::score::ExtraLifeEarned;
+use crate::control::{Pawn, PlayerController, ShipPawn};
+use crate::ship::Ship;
+use crate::save::BodyState;
+use crate::spawner::SpawnGenerator;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerupKind {
+    Shield,
+    RapidFire,
+    SpreadShot,
+    ExtraLife,
+    SmartBomb,
+}
+
+// what an asteroid of one size might leave behind
+#[derive(Deserialize, Clone)]
+struct DropTable {
+    chance: f32,
+    weights: Vec<(PowerupKind, u32)>,
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct PowerupConfig {
+    lifetime: u64,
+    size: f32,
+    radius: f32,
+    drift: f32,
+    colors: HashMap<PowerupKind, (f32, f32, f32)>,
+    shield_duration: u64,
+    rapid_fire_duration: u64,
+    rapid_fire_factor: f32,
+    spread_duration: u64,
+    spread_angle: f32,
+    extra_life_grace: u64,
+    // one per asteroid size, in the order a.ast.ron lists them
+    drops: Vec<DropTable>,
+}
+
+impl PowerupConfig {
+    fn roll(&self, size: Option<usize>, spawner: &mut SpawnGenerator) -> Option<PowerupKind> {
+        let table = self.drops.get(size?)?;
+        let total: u32 = table.weights.iter().map(|(_, weight)| weight).sum();
+        if total == 0 || spawner.rng.f32() >= table.chance {
+            return None;
+        }
+        let mut pick = spawner.rng.u32(0..total);
+        for (kind, weight) in table.weights.iter() {
+            if pick < *weight {
+                return Some(*kind);
+            }
+            pick -= weight;
+        }
+        None
+    }
+
+    // everything but the smart bomb, which goes off for the whole team and is sent by the caller
+    fn grant(&self, kind: PowerupKind, ship: &mut EntityCommands, lives: Option<&ExtraLives>) {
+        match kind {
+            PowerupKind::Shield => {
+                shield(ship, Duration::from_millis(self.shield_duration));
+            }
+            PowerupKind::RapidFire => {
+                ship.insert(RapidFire {
+                    timer: timer(self.rapid_fire_duration),
+                    factor: self.rapid_fire_factor,
+                });
+            }
+            PowerupKind::SpreadShot => {
+                ship.insert(SpreadShot {
+                    timer: timer(self.spread_duration),
+                    angle: self.spread_angle,
+                });
+            }
+            PowerupKind::ExtraLife => {
+                ship.insert(ExtraLives {
+                    count: lives.map_or(0, |lives| lives.count) + 1,
+                    grace: Duration::from_millis(self.extra_life_grace),
+                });
+            }
+            PowerupKind::SmartBomb => (),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PowerupConfigHandle {
+    config: Handle<PowerupConfig>,
+}
+
+#[derive(Resource)]
+struct PowerupAssets {
+    mesh: Handle<Mesh>,
+    materials: HashMap<PowerupKind, Handle<ColorMaterial>>,
+}
+
+#[derive(Component)]
+pub struct Pickup(pub PowerupKind);
+
+// effects a ship picks up, each wears off on its own timer
+
+// nothing hurts the ship
+#[derive(Component)]
+pub struct Shielded(pub Timer);
+
+#[derive(Component)]
+pub struct RapidFire {
+    pub timer: Timer,
+    pub factor: f32,
+}
+
+// every gun fires a bullet either side as well
+#[derive(Component)]
+pub struct SpreadShot {
+    pub timer: Timer,
+    pub angle: f32,
+}
+
+// shields the ship for at least this long, a longer shield it already has is kept
+pub fn shield(ship: &mut EntityCommands, duration: Duration) {
+    ship.entry::<Shielded>()
+        .and_modify(move |mut shielded| {
+            if shielded.0.remaining() < duration {
+                shielded.0 = Timer::new(duration, TimerMode::Once);
+            }
+        })
+        .or_insert(Shielded(Timer::new(duration, TimerMode::Once)));
+}
+
+// a hit spends one of these instead of ending the game
+#[derive(Component)]
+pub struct ExtraLives {
+    pub count: u32,
+    pub grace: Duration,
+}
+
+// a ship's effects as saved, timers as the time they have left
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EffectsState {
+    shielded: Option<Duration>,
+    rapid_fire: Option<(Duration, f32)>,
+    spread_shot: Option<(Duration, f32)>,
+    extra_lives: Option<(u32, Duration)>,
+}
+
+pub fn effects_state(world: &World, ship: Entity) -> EffectsState {
+    EffectsState {
+        shielded: world.get::<Shielded>(ship).map(|shielded| shielded.0.remaining()),
+        rapid_fire: world
+            .get::<RapidFire>(ship)
+            .map(|rapid_fire| (rapid_fire.timer.remaining(), rapid_fire.factor)),
+        spread_shot: world
+            .get::<SpreadShot>(ship)
+            .map(|spread_shot| (spread_shot.timer.remaining(), spread_shot.angle)),
+        extra_lives: world
+            .get::<ExtraLives>(ship)
+            .map(|lives| (lives.count, lives.grace)),
+    }
+}
+
+pub fn restore_effects(world: &mut World, ship: Entity, effects: &EffectsState) {
+    let mut ship = world.entity_mut(ship);
+    if let Some(left) = effects.shielded {
+        ship.insert(Shielded(Timer::new(left, TimerMode::Once)));
+    }
+    if let Some((left, factor)) = effects.rapid_fire {
+        ship.insert(RapidFire {
+            timer: Timer::new(left, TimerMode::Once),
+            factor,
+        });
+    }
+    if let Some((left, angle)) = effects.spread_shot {
+        ship.insert(SpreadShot {
+            timer: Timer::new(left, TimerMode::Once),
+            angle,
+        });
+    }
+    if let Some((count, grace)) = effects.extra_lives {
+        ship.insert(ExtraLives { count, grace });
+    }
+}
+
+trait TimedEffect: Component {
+    fn timer(&mut self) -> &mut Timer;
+}
+
+impl TimedEffect for Shielded {
+    fn timer(&mut self) -> &mut Timer {
+        &mut self.0
+    }
+}
+
+impl TimedEffect for RapidFire {
+    fn timer(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+}
+
+impl TimedEffect for SpreadShot {
+    fn timer(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+}
+
+#[derive(Bundle)]
+struct PickupBundle {
+    pickup: Pickup,
+    position: Position,
+    velocity: Velocity,
+    rotation: Rotation,
+    angular_velocity: AngularVelocity,
+    scale: Scale,
+    spawn_time: TimeStamp,
+    rigid_body: RigidBody,
+    collider: Collider,
+}
+
+impl PickupBundle {
+    fn new(kind: PowerupKind, position: Vec2, velocity: Vec2, radius: f32, spawn_time: Duration) -> Self {
+        Self {
+            pickup: Pickup(kind),
+            position: Position(position),
+            velocity: Velocity(velocity),
+            rotation: Rotation(0.0),
+            angular_velocity: AngularVelocity(1.5),
+            scale: Scale(1.0),
+            spawn_time: TimeStamp(spawn_time),
+            rigid_body: RigidBody {
+                radius,
+                mass: 1.0,
+            },
+            collider: Collider { team: PICKUP_TEAM },
+        }
+    }
+}
+
+pub fn restore_pickup(
+    world: &mut World,
+    body: &BodyState,
+    kind: PowerupKind,
+    spawn_time: Duration,
+) -> Option<Entity> {
+    let handle = world.get_resource::<PowerupConfigHandle>()?;
+    let radius = world.resource::<Assets<PowerupConfig>>().get(handle.config.id())?.radius;
+    let assets = world.get_resource::<PowerupAssets>()?;
+    let mesh = assets.mesh.clone();
+    let material = assets.materials.get(&kind)?.clone();
+
+    let mut bundle = PickupBundle::new(kind, body.position, body.velocity, radius, spawn_time);
+    bundle.rotation = Rotation(body.rotation);
+    bundle.angular_velocity = AngularVelocity(body.angular_velocity);
+    bundle.scale = Scale(body.scale);
+    let entity = world.spawn((
+        bundle,
+        Mesh2d(mesh),
+        MeshMaterial2d(material),
+        Transform::default(),
+    ));
+    Some(entity.id())
+}
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.powerup.ron");
+    commands.insert_resource(PowerupConfigHandle { config });
+}
+
+fn load_powerups(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    configs: Res<Assets<PowerupConfig>>,
+    config_handle: Res<PowerupConfigHandle>,
+    powerup_assets: Option<Res<PowerupAssets>>,
+) {
+    if powerup_assets.is_some() {
+        return;
+    }
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        let mesh = meshes.add(RegularPolygon::new(config.size, 6));
+        let materials = config
+            .colors
+            .iter()
+            .map(|(kind, color)| (*kind, materials.add(Color::srgb(color.0, color.1, color.2))))
+            .collect();
+        commands.insert_resource(PowerupAssets { mesh, materials });
+    }
+}
+
+fn drop_pickups(
+    mut commands: Commands,
+    mut events: EventReader<AsteroidDestroyed>,
+    mut spawner: ResMut<SpawnGenerator>,
+    powerup_assets: Option<Res<PowerupAssets>>,
+    time: Res<Time>,
+    configs: Res<Assets<PowerupConfig>>,
+    config_handle: Res<PowerupConfigHandle>,
+) {
+    let (Some(assets), Some(config)) = (powerup_assets, configs.get(config_handle.config.id())) else {
+        return;
+    };
+    for event in events.read() {
+        let Some(kind) = config.roll(event.size, &mut spawner) else {
+            continue;
+        };
+        let Some(material) = assets.materials.get(&kind) else {
+            continue;
+        };
+        commands.spawn((
+            PickupBundle::new(
+                kind,
+                event.position,
+                event.velocity * config.drift,
+                config.radius,
+                time.elapsed(),
+            ),
+            Mesh2d(assets.mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            Transform::default(),
+        ));
+    }
+}
+
+fn expire_pickups(
+    mut commands: Commands,
+    pickups: Query<(Entity, &TimeStamp), With<Pickup>>,
+    time: Res<Time>,
+    configs: Res<Assets<PowerupConfig>>,
+    config_handle: Res<PowerupConfigHandle>,
+) {
+    if let Some(config) = configs.get(config_handle.config.id()) {
+        let time_elapsed = time.elapsed();
+        for (entity, spawn_time) in &pickups {
+            if time_elapsed.saturating_sub(spawn_time.0) > Duration::from_millis(config.lifetime) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    pickups: Query<&Pickup>,
+    ships: Query<(Option<&ExtraLives>, &Collider, &ShipPawn), With<Ship>>,
+    mut collisions: EventReader<Collision>,
+    mut smart_bomb: EventWriter<SmartBomb>,
+    configs: Res<Assets<PowerupConfig>>,
+    config_handle: Res<PowerupConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    // a pickup can touch two ships at once, only the first gets it
+    let mut collected = Vec::new();
+    for event in collisions.read() {
+        for (entity_a, entity_b) in [
+            (event.entity1, event.entity2),
+            (event.entity2, event.entity1),
+        ] {
+            let (Ok(pickup), Ok((lives, collider, pawn))) = (pickups.get(entity_a), ships.get(entity_b))
+            else {
+                continue;
+            };
+            if collected.contains(&entity_a) {
+                continue;
+            }
+            collected.push(entity_a);
+            commands.entity(entity_a).despawn();
+
+            match pickup.0 {
+                PowerupKind::SmartBomb => {
+                    smart_bomb.send(SmartBomb {
+                        team: collider.team,
+                        player: Some(*pawn.get_controller()),
+                    });
+                }
+                kind => config.grant(kind, &mut commands.entity(entity_b), lives),
+            }
+        }
+    }
+}
+
+// lives earned from the score go to every ship on the team still flying
+fn award_extra_lives(
+    mut commands: Commands,
+    mut events: EventReader<ExtraLifeEarned>,
+    ships: Query<(Entity, &Collider, Option<&ExtraLives>), With<Ship>>,
+    configs: Res<Assets<PowerupConfig>>,
+    config_handle: Res<PowerupConfigHandle>,
+) {
+    let mut earned: HashMap<u32, u32> = HashMap::new();
+    for event in events.read() {
+        *earned.entry(event.team).or_default() += 1;
+    }
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    for (ship, collider, lives) in ships.iter() {
+        let Some(earned) = earned.get(&collider.team) else {
+            continue;
+        };
+        commands.entity(ship).insert(ExtraLives {
+            count: lives.map_or(0, |lives| lives.count) + earned,
+            grace: Duration::from_millis(config.extra_life_grace),
+        });
+    }
+}
+
+fn timer(millis: u64) -> Timer {
+    Timer::new(Duration::from_millis(millis), TimerMode::Once)
+}
+
+fn wear_off<T: TimedEffect>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut T)>,
+) {
+    for (entity, mut effect) in effects.iter_mut() {
+        if effect.timer().tick(time.delta()).finished() {
+            commands.entity(entity).remove::<T>();
+        }
+    }
+}
+
+fn give_command(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    ships: Query<(Entity, Option<&ExtraLives>, &ShipPawn), With<Ship>>,
+    players: Query<(), With<PlayerController>>,
+    mut cheated: ResMut<Cheated>,
+    configs: Res<Assets<PowerupConfig>>,
+    config_handle: Res<PowerupConfigHandle>,
+) -> String {
+    // "give weapon SpreadShot" reads the same as "give SpreadShot"
+    let Some(Ok(kind)) = args.last().map(|name| ron::from_str::<PowerupKind>(name)) else {
+        return "give [weapon] <Shield|RapidFire|SpreadShot|ExtraLife>".to_string();
+    };
+    if kind == PowerupKind::SmartBomb {
+        return "a smart bomb can't be carried, kill all clears the field".to_string();
+    }
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return "powerups haven't loaded yet".to_string();
+    };
+    let mut given = 0;
+    for (ship, lives, pawn) in ships.iter() {
+        if players.contains(*pawn.get_controller()) {
+            config.grant(kind, &mut commands.entity(ship), lives);
+            given += 1;
+        }
+    }
+    cheated.0 = true;
+    format!("gave {:?} to {} ships", kind, given)
+}
+
+fn despawn_pickups(mut commands: Commands, pickups: Query<Entity, With<Pickup>>) {
+    for entity in pickups.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub struct PowerupPlugin;
+
+impl Plugin for PowerupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<PowerupConfig>(&["powerup.ron"]);
+        app.add_systems(Startup, load_config);
+        app.add_systems(Update, (load_powerups).in_set(InGameSet::LoadEntities));
+        app.add_systems(
+            Update,
+            // a collected pickup is gone before expiry looks, so none is despawned twice
+            (collect_pickups, expire_pickups)
+                .chain()
+                .in_set(InGameSet::DespawnEntities),
+        );
+        app.add_systems(
+            Update,
+            (
+                wear_off::<Shielded>,
+                wear_off::<RapidFire>,
+                wear_off::<SpreadShot>,
+                award_extra_lives,
+            )
+                .in_set(InGameSet::UpdateEntities),
+        );
+        app.add_systems(Update, (drop_pickups).in_set(InGameSet::CollisionReaction));
+        app.add_systems(OnEnter(GameState::GameOver), despawn_pickups);
+        app.add_console_command("give", "give [weapon] <powerup>, to every player's ship", give_command);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::control::{controller_id, ReplayController};
+use crate::input_actions::*;
+use crate::netplay::ready;
+use crate::schedule::InGameSet;
+use crate::ship::Pilot;
+use crate::ship_select::ShipSelect;
+use crate::WorldSeed;
+use bevy::app::AppExit;
+use bevy::ecs::event::EventCursor;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// recording and playback step by exactly this much, so the same inputs land on the same frames
+const FRAME: Duration = Duration::from_nanos(16_666_667);
+// bump whenever the layout below changes
+const REPLAY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedPilot {
+    pub controller: u32,
+    pub team: u32,
+    pub spawn: Vec2,
+    #[serde(default)]
+    pub ship: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedInput {
+    // counted from the first frame everything had loaded, loading takes however long it takes
+    pub frame: u32,
+    pub controller: u32,
+    pub input: Input,
+}
+
+// a run from the seed up, replays only match with the same configs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub pilots: Vec<RecordedPilot>,
+    // the run began on the ship select screen, playback has to as well
+    #[serde(default)]
+    pub ship_select: bool,
+    pub inputs: Vec<RecordedInput>,
+    pub frames: u32,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let replay = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| ron::from_str::<Replay>(&text).map_err(|e| e.to_string()))?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!(
+                "replay is version {}, expected {}",
+                replay.version, REPLAY_VERSION
+            ));
+        }
+        Ok(replay)
+    }
+}
+
+#[derive(Resource)]
+struct Recording {
+    path: PathBuf,
+    replay: Replay,
+    // None until everything has loaded
+    frame: Option<u32>,
+}
+
+#[derive(Resource)]
+struct Playback {
+    replay: Replay,
+    frame: Option<u32>,
+    next: usize,
+}
+
+fn record_frame(world: &mut World, mut cursor: Local<EventCursor<InputEvent>>) {
+    let frame = world.resource::<Recording>().frame;
+    let frame = match frame {
+        Some(frame) => frame,
+        None if ready(world) => {
+            let mut pilots = world.query::<(Entity, &Pilot)>();
+            let pilots: Vec<RecordedPilot> = pilots
+                .iter(world)
+                .filter_map(|(entity, pilot)| {
+                    Some(RecordedPilot {
+                        controller: controller_id(world, entity)?,
+                        team: pilot.team,
+                        spawn: pilot.spawn,
+                        ship: pilot.ship,
+                    })
+                })
+                .collect();
+            let ship_select = world.contains_resource::<ShipSelect>();
+            let mut recording = world.resource_mut::<Recording>();
+            recording.replay.pilots = pilots;
+            recording.replay.ship_select = ship_select;
+            0
+        }
+        None => {
+            // skip anything pressed while loading
+            cursor.clear(world.resource::<Events<InputEvent>>());
+            return;
+        }
+    };
+
+    let events = world.resource::<Events<InputEvent>>();
+    let inputs: Vec<RecordedInput> = cursor
+        .read(events)
+        .filter_map(|event| {
+            Some(RecordedInput {
+                frame,
+                controller: controller_id(world, event.controller)?,
+                input: event.input,
+            })
+        })
+        .collect();
+    let mut recording = world.resource_mut::<Recording>();
+    recording.replay.inputs.extend(inputs);
+    recording.replay.frames = frame + 1;
+    recording.frame = Some(frame + 1);
+}
+
+fn save_recording(recording: Res<Recording>, mut exits: EventReader<AppExit>) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    let result = ron::to_string(&recording.replay)
+        .map_err(|e| e.to_string())
+        .and_then(|text| std::fs::write(&recording.path, text).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => info!("recorded {} frames to {}", recording.replay.frames, recording.path.display()),
+        Err(e) => warn!("could not save recording: {}", e),
+    }
+}
+
+// the keyboard sits a replay out, a pause it didn't record would throw the timing off
+fn spawn_replay_pilots(mut commands: Commands, playback: Res<Playback>) {
+    for pilot in playback.replay.pilots.iter() {
+        commands.spawn((
+            ReplayController {
+                id: pilot.controller,
+            },
+            Pilot {
+                team: pilot.team,
+                spawn: pilot.spawn,
+                ship: pilot.ship,
+            },
+        ));
+    }
+}
+
+fn play_frame(world: &mut World, mut controllers: Local<HashMap<u32, Entity>>) {
+    let frame = world.resource::<Playback>().frame;
+    let frame = match frame {
+        Some(frame) => frame,
+        None if ready(world) => 0,
+        None => return,
+    };
+
+    let playback = world.resource::<Playback>();
+    let finished = frame == playback.replay.frames;
+    let mut next = playback.next;
+    let mut due = Vec::new();
+    while let Some(recorded) = playback.replay.inputs.get(next) {
+        if recorded.frame > frame {
+            break;
+        }
+        due.push((recorded.controller, recorded.input));
+        next += 1;
+    }
+
+    if finished {
+        info!("replay finished after {} frames", frame);
+        // nobody is watching a headless replay, it was only run to see where it ends up
+        let mut windows = world.query::<&Window>();
+        if windows.iter(world).next().is_none() {
+            world.send_event(AppExit::Success);
+        }
+    }
+
+    if controllers.is_empty() {
+        let mut replayed = world.query::<(Entity, &ReplayController)>();
+        controllers.extend(replayed.iter(world).map(|(entity, controller)| (controller.id, entity)));
+    }
+    for (id, input) in due {
+        // controllers without a ship, like the one that paused, only turn up in the inputs
+        let controller = *controllers
+            .entry(id)
+            .or_insert_with(|| world.spawn(ReplayController { id }).id());
+        world.send_event(InputEvent { controller, input });
+    }
+
+    let mut playback = world.resource_mut::<Playback>();
+    playback.next = next;
+    playback.frame = Some(frame + 1);
+}
+
+// writes every input to the file on exit
+//   asteroids --record run.ron
+pub struct RecordPlugin {
+    pub path: PathBuf,
+}
+
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = app.world().resource::<WorldSeed>().0;
+        app.insert_resource(Recording {
+            path: self.path.clone(),
+            replay: Replay {
+                version: REPLAY_VERSION,
+                seed,
+                pilots: Vec::new(),
+                ship_select: false,
+                inputs: Vec::new(),
+                frames: 0,
+            },
+            frame: None,
+        });
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+        app.add_systems(
+            Update,
+            record_frame
+                .after(InGameSet::GameInput)
+                .before(InGameSet::UpdateEntities),
+        );
+        app.add_systems(Last, save_recording);
+    }
+}
+
+// plays a recording back in place of the keyboard, insert the replay's seed as the WorldSeed
+//   asteroids --replay run.ron
+pub struct ReplayPlugin {
+    pub replay: Replay,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Playback {
+            replay: self.replay.clone(),
+            frame: None,
+            next: 0,
+        });
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+        app.add_systems(PreStartup, spawn_replay_pilots);
+        app.add_systems(Update, play_frame.in_set(InGameSet::GameInput));
+    }
+}
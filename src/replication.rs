@@ -0,0 +1,148 @@
+use crate::input_actions::*;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// the server steps this many times a second, clients pace interpolation off it
+pub const TICK_RATE: f32 = 60.0;
+
+// what a client needs to know to draw an entity
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Kind {
+    Asteroid { variant: usize },
+    Ship { team: u32, ship: usize },
+    Bullet { team: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Replica {
+    pub kind: Kind,
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+    pub scale: f32,
+}
+
+// entities by server id, as last sent to or received by a client
+pub type WorldView = HashMap<u64, Replica>;
+
+// only the fields that changed since the last frame the client has
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityDelta {
+    pub id: u64,
+    pub kind: Option<Kind>,
+    pub position: Option<Vec2>,
+    pub rotation: Option<f32>,
+    pub velocity: Option<Vec2>,
+    pub scale: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameDelta {
+    pub tick: u32,
+    pub state: GameState,
+    // by team
+    pub scores: BTreeMap<u32, u64>,
+    pub changed: Vec<EntityDelta>,
+    pub removed: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Join { spectate: bool },
+    // the inputs the client is holding, kept until the next message replaces them
+    Input(Vec<Input>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServerMessage {
+    // pilot is None for spectators, and when every ship is taken
+    Welcome { pilot: Option<u32> },
+    Frame(FrameDelta),
+}
+
+// actions a remote player may send, pausing is left to the server
+pub fn remote_action(action: Actions) -> bool {
+    matches!(
+        action,
+        Actions::MoveForward
+            | Actions::Rotate
+            | Actions::Shoot
+            | Actions::Shield
+            | Actions::Heading
+            | Actions::Aim
+            | Actions::Restart
+    )
+}
+
+fn changed<T: PartialEq>(previous: T, current: T) -> Option<T> {
+    (previous != current).then_some(current)
+}
+
+pub fn diff(old: &WorldView, new: &WorldView) -> (Vec<EntityDelta>, Vec<u64>) {
+    let mut deltas = Vec::new();
+    for (id, replica) in new {
+        let delta = match old.get(id) {
+            Some(previous) if previous == replica => continue,
+            Some(previous) => EntityDelta {
+                id: *id,
+                kind: changed(previous.kind, replica.kind),
+                position: changed(previous.position, replica.position),
+                rotation: changed(previous.rotation, replica.rotation),
+                velocity: changed(previous.velocity, replica.velocity),
+                scale: changed(previous.scale, replica.scale),
+            },
+            None => EntityDelta {
+                id: *id,
+                kind: Some(replica.kind),
+                position: Some(replica.position),
+                rotation: Some(replica.rotation),
+                velocity: Some(replica.velocity),
+                scale: Some(replica.scale),
+            },
+        };
+        deltas.push(delta);
+    }
+    let removed = old.keys().filter(|id| !new.contains_key(id)).copied().collect();
+    (deltas, removed)
+}
+
+pub fn apply(view: &mut WorldView, frame: &FrameDelta) {
+    for id in &frame.removed {
+        view.remove(id);
+    }
+    for delta in &frame.changed {
+        // a new entity always arrives with every field
+        if let (false, Some(kind)) = (view.contains_key(&delta.id), delta.kind) {
+            view.insert(
+                delta.id,
+                Replica {
+                    kind,
+                    position: Vec2::ZERO,
+                    rotation: 0.0,
+                    velocity: Vec2::ZERO,
+                    scale: 1.0,
+                },
+            );
+        }
+        let Some(replica) = view.get_mut(&delta.id) else {
+            continue;
+        };
+        if let Some(kind) = delta.kind {
+            replica.kind = kind;
+        }
+        if let Some(position) = delta.position {
+            replica.position = position;
+        }
+        if let Some(rotation) = delta.rotation {
+            replica.rotation = rotation;
+        }
+        if let Some(velocity) = delta.velocity {
+            replica.velocity = velocity;
+        }
+        if let Some(scale) = delta.scale {
+            replica.scale = scale;
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use crate::achievements::RunProgress;
+use crate::asteroid::{restore_asteroid, Asteroid, MeshVariant, SpawnTimer};
+use crate::bodies::*;
+use crate::bullet::{restore_bullet, Bullet, FiredBy};
+use crate::control::{
+    controller_id, AgentController, AiController, Controller, NetController, Pawn,
+    PlayerController, ReplayController, ShipPawn,
+};
+use crate::daily::DailyRun;
+use crate::input_actions::*;
+use crate::powerup::{
+    effects_state, restore_effects, restore_pickup, EffectsState, Pickup, PowerupKind,
+};
+use crate::schedule::InGameSet;
+use crate::score::{Combo, Score, ScorePopup, Streak};
+use crate::shield::{restore_shield, Shield};
+use crate::ship::{restore_ship, Pilot, Ship, ShipKind};
+use crate::spawner::SpawnGenerator;
+use crate::stats::RunStats;
+use crate::GameState;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_turborand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// bump whenever the layout below changes, older saves are refused rather than misread
+pub const SAVE_VERSION: u32 = 3;
+const SAVE_PATH: &str = "savegame.ron";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyState {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    pub scale: f32,
+}
+
+// times are stored as ages so they survive the clock restarting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EntityState {
+    Asteroid {
+        body: BodyState,
+        variant: usize,
+    },
+    Ship {
+        body: BodyState,
+        controller: u32,
+        team: u32,
+        // saves from before the roster fly the first ship
+        #[serde(default)]
+        kind: usize,
+        last_shot_age: Duration,
+        // saves from before pickups fly without any
+        #[serde(default)]
+        effects: EffectsState,
+        // energy and whether it's up, none for a ship not fitted with one yet
+        #[serde(default)]
+        shield: Option<(f32, bool)>,
+    },
+    Bullet {
+        body: BodyState,
+        team: u32,
+        age: Duration,
+        // the controller id of whoever fired it, so a kill after a load still scores for them
+        #[serde(default)]
+        shooter: Option<u32>,
+    },
+    Pickup {
+        body: BodyState,
+        kind: PowerupKind,
+        age: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    // by team, then by controller id
+    pub scores: BTreeMap<u32, u64>,
+    pub players: Vec<(u32, u64)>,
+    // controller id, kills and how long since the last, saves from before combos start without one
+    #[serde(default)]
+    pub combos: Vec<(u32, u32, Duration)>,
+    #[serde(default)]
+    pub stats: RunStats,
+    #[serde(default)]
+    pub progress: RunProgress,
+    // the spawner is reseeded from this on save and load, so both continue identically
+    pub seed: u64,
+    pub spawn_timer: Duration,
+    pub entities: Vec<EntityState>,
+}
+
+fn body_state(
+    position: &Position,
+    velocity: &Velocity,
+    rotation: &Rotation,
+    angular_velocity: &AngularVelocity,
+    scale: &Scale,
+) -> BodyState {
+    BodyState {
+        position: position.0,
+        velocity: velocity.0,
+        rotation: rotation.0,
+        angular_velocity: angular_velocity.0,
+        scale: scale.0,
+    }
+}
+
+pub fn take_snapshot(world: &mut World) -> Snapshot {
+    let elapsed = world.resource::<Time>().elapsed();
+    let mut entities = Vec::new();
+
+    let mut asteroids = world.query_filtered::<(
+        &Position,
+        &Velocity,
+        &Rotation,
+        &AngularVelocity,
+        &Scale,
+        &MeshVariant,
+    ), With<Asteroid>>();
+    for (position, velocity, rotation, angular_velocity, scale, variant) in asteroids.iter(world) {
+        entities.push(EntityState::Asteroid {
+            body: body_state(position, velocity, rotation, angular_velocity, scale),
+            variant: variant.0,
+        });
+    }
+
+    let mut ships = world.query_filtered::<(
+        Entity,
+        &Position,
+        &Velocity,
+        &Rotation,
+        &AngularVelocity,
+        &Scale,
+        &TimeStamp,
+        &ShipPawn,
+        &Collider,
+        &ShipKind,
+    ), With<Ship>>();
+    for (
+        entity,
+        position,
+        velocity,
+        rotation,
+        angular_velocity,
+        scale,
+        last_shot,
+        pawn,
+        collider,
+        kind,
+    ) in ships.iter(world)
+    {
+        let controller = controller_id(world, *pawn.get_controller()).unwrap_or(0);
+        entities.push(EntityState::Ship {
+            body: body_state(position, velocity, rotation, angular_velocity, scale),
+            controller,
+            team: collider.team,
+            kind: kind.0,
+            last_shot_age: elapsed.saturating_sub(last_shot.0),
+            effects: effects_state(world, entity),
+            shield: world
+                .get::<Shield>(entity)
+                .map(|shield| (shield.energy, shield.raised)),
+        });
+    }
+
+    let mut bullets = world.query_filtered::<(
+        &Position,
+        &Velocity,
+        &Rotation,
+        &AngularVelocity,
+        &Scale,
+        &TimeStamp,
+        &Collider,
+        Option<&FiredBy>,
+    ), With<Bullet>>();
+    for (position, velocity, rotation, angular_velocity, scale, spawn_time, collider, fired_by) in
+        bullets.iter(world)
+    {
+        entities.push(EntityState::Bullet {
+            body: body_state(position, velocity, rotation, angular_velocity, scale),
+            team: collider.team,
+            age: elapsed.saturating_sub(spawn_time.0),
+            shooter: fired_by.and_then(|fired_by| controller_id(world, fired_by.0)),
+        });
+    }
+
+    let mut pickups = world.query::<(
+        &Position,
+        &Velocity,
+        &Rotation,
+        &AngularVelocity,
+        &Scale,
+        &TimeStamp,
+        &Pickup,
+    )>();
+    for (position, velocity, rotation, angular_velocity, scale, spawn_time, pickup) in
+        pickups.iter(world)
+    {
+        entities.push(EntityState::Pickup {
+            body: body_state(position, velocity, rotation, angular_velocity, scale),
+            kind: pickup.0,
+            age: elapsed.saturating_sub(spawn_time.0),
+        });
+    }
+
+    let mut spawner = world.resource_mut::<SpawnGenerator>();
+    let seed = spawner.rng.u64(..);
+    spawner.rng = RngComponent::with_seed(seed);
+
+    let score = world.resource::<Score>();
+    let scores = score.teams.clone();
+    let mut players: Vec<(u32, u64)> = score
+        .players
+        .iter()
+        .filter_map(|(player, points)| Some((controller_id(world, *player)?, *points)))
+        .collect();
+    // the same scores always come out in the same order, netplay checksums them
+    players.sort();
+    let mut combos: Vec<(u32, u32, Duration)> = world
+        .resource::<Combo>()
+        .players
+        .iter()
+        .filter_map(|(player, streak)| {
+            let age = elapsed.saturating_sub(streak.last);
+            Some((controller_id(world, *player)?, streak.kills, age))
+        })
+        .collect();
+    combos.sort();
+
+    Snapshot {
+        version: SAVE_VERSION,
+        scores,
+        players,
+        combos,
+        stats: world.resource::<RunStats>().clone(),
+        progress: world.resource::<RunProgress>().clone(),
+        seed,
+        spawn_timer: world.resource::<SpawnTimer>().0.elapsed(),
+        entities,
+    }
+}
+
+// only pilots own ships, a controller without one might share the id
+fn find_controller(world: &mut World, id: u32) -> Entity {
+    let mut controllers = world.query_filtered::<(
+        Entity,
+        Option<&PlayerController>,
+        Option<&NetController>,
+        Option<&AgentController>,
+        Option<&AiController>,
+        Option<&ReplayController>,
+    ), With<Pilot>>();
+    let found = controllers.iter(world).find(|(_, player, net, agent, ai, replay)| {
+        player
+            .map(|c| *c.get_id())
+            .or(net.map(|c| *c.get_id()))
+            .or(agent.map(|c| *c.get_id()))
+            .or(ai.map(|c| *c.get_id()))
+            .or(replay.map(|c| *c.get_id()))
+            == Some(id)
+    });
+    if let Some((entity, _, _, _, _, _)) = found {
+        return entity;
+    }
+    world.spawn((PlayerController { id }, Pilot::default())).id()
+}
+
+pub fn restore_snapshot(world: &mut World, snapshot: &Snapshot) {
+    // popups too, a rollback spawns the ones for the frames it redoes again
+    let mut gameplay = world.query_filtered::<Entity, Or<(
+        With<Asteroid>,
+        With<Ship>,
+        With<Bullet>,
+        With<Pickup>,
+        With<ScorePopup>,
+    )>>();
+    let stale: Vec<Entity> = gameplay.iter(world).collect();
+    for entity in stale {
+        world.despawn(entity);
+    }
+
+    let players = snapshot
+        .players
+        .iter()
+        .map(|(id, points)| (find_controller(world, *id), *points))
+        .collect();
+    *world.resource_mut::<Score>() = Score {
+        teams: snapshot.scores.clone(),
+        players,
+    };
+    let elapsed = world.resource::<Time>().elapsed();
+    let combos = snapshot
+        .combos
+        .iter()
+        .map(|(id, kills, age)| {
+            let streak = Streak {
+                kills: *kills,
+                last: elapsed.saturating_sub(*age),
+            };
+            (find_controller(world, *id), streak)
+        })
+        .collect();
+    *world.resource_mut::<Combo>() = Combo { players: combos };
+    *world.resource_mut::<RunStats>() = snapshot.stats.clone();
+    *world.resource_mut::<RunProgress>() = snapshot.progress.clone();
+    world.resource_mut::<SpawnGenerator>().rng = RngComponent::with_seed(snapshot.seed);
+    world.resource_mut::<SpawnTimer>().0.set_elapsed(snapshot.spawn_timer);
+
+    for entity in &snapshot.entities {
+        match entity {
+            EntityState::Asteroid { body, variant } => {
+                restore_asteroid(world, body, *variant);
+            }
+            EntityState::Ship {
+                body,
+                controller,
+                team,
+                kind,
+                last_shot_age,
+                effects,
+                shield,
+            } => {
+                let controller = find_controller(world, *controller);
+                let last_shot = elapsed.saturating_sub(*last_shot_age);
+                let ship = restore_ship(world, body, controller, *team, *kind, last_shot);
+                restore_effects(world, ship, effects);
+                if let Some((energy, raised)) = shield {
+                    restore_shield(world, ship, *energy, *raised);
+                }
+            }
+            EntityState::Bullet {
+                body,
+                team,
+                age,
+                shooter,
+            } => {
+                let shooter = shooter.map(|id| find_controller(world, id));
+                restore_bullet(world, body, *team, elapsed.saturating_sub(*age), shooter);
+            }
+            EntityState::Pickup { body, kind, age } => {
+                restore_pickup(world, body, *kind, elapsed.saturating_sub(*age));
+            }
+        }
+    }
+}
+
+fn save_game(world: &mut World) {
+    let snapshot = take_snapshot(world);
+    let result = ron::ser::to_string_pretty(&snapshot, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|text| std::fs::write(SAVE_PATH, text).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => info!("saved game to {}", SAVE_PATH),
+        Err(e) => warn!("could not save game: {}", e),
+    }
+}
+
+fn load_game(world: &mut World) {
+    let snapshot = std::fs::read_to_string(SAVE_PATH)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ron::from_str::<Snapshot>(&text).map_err(|e| e.to_string()));
+    match snapshot {
+        Ok(snapshot) if snapshot.version == SAVE_VERSION => {
+            restore_snapshot(world, &snapshot);
+            world.resource_mut::<NextState<GameState>>().set(GameState::InGame);
+            info!("loaded game from {}", SAVE_PATH);
+        }
+        Ok(snapshot) => warn!(
+            "save is version {}, expected {}",
+            snapshot.version, SAVE_VERSION
+        ),
+        Err(e) => warn!("could not load game: {}", e),
+    }
+}
+
+// saving and loading are offered from the pause menu
+fn pause_menu(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    mut events: EventReader<InputEvent>,
+) {
+    for event in events.read() {
+        if *state.get() != GameState::Paused {
+            continue;
+        }
+        match event.input.action {
+            Actions::Save => commands.queue(save_game),
+            Actions::Load => commands.queue(load_game),
+            _ => (),
+        }
+    }
+}
+
+// quitting mid game leaves a save to pick up from
+fn autosave(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    mut exits: EventReader<AppExit>,
+    ships: Query<&ShipPawn, With<Ship>>,
+    players: Query<(), With<PlayerController>>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    let playing = matches!(state.get(), GameState::InGame | GameState::Paused);
+    // only a game flown by local players, a remote or half set up world would overwrite the real save
+    let local = !ships.is_empty()
+        && ships
+            .iter()
+            .all(|pawn| players.contains(*pawn.get_controller()));
+    if playing && local {
+        commands.queue(save_game);
+    }
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        // a daily run is one attempt, there's nothing to go back to
+        app.add_systems(
+            Update,
+            (pause_menu)
+                .run_if(not(resource_exists::<DailyRun>))
+                .in_set(InGameSet::GameInput),
+        );
+        app.add_systems(Last, autosave.run_if(not(resource_exists::<DailyRun>)));
+    }
+}
@@ -1,6 +1,7 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
-use crate::GameState;
+use crate::{GameState, Grid};
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum InGameSet {
@@ -14,6 +15,31 @@ pub enum InGameSet {
     RenderSetup,
 }
 
+// present while netplay re-runs frames after a rollback, they were seen and heard the first time
+#[derive(Resource)]
+pub struct Resimulating;
+
+// for sounds and effects that shouldn't play twice for the same frame, anything the game keeps
+// count of belongs in the snapshot instead so a rollback takes it back
+pub fn resimulating(resimulating: Option<Res<Resimulating>>) -> bool {
+    resimulating.is_some()
+}
+
+// events only update between real frames, so a system skipped while resimulating would get
+// every redone frame's events on the next one, this reads them all and hands out the new ones
+#[derive(SystemParam)]
+pub struct FreshEvents<'w, 's, E: Event> {
+    events: EventReader<'w, 's, E>,
+    resimulating: Option<Res<'w, Resimulating>>,
+}
+
+impl<E: Event> FreshEvents<'_, '_, E> {
+    pub fn read(&mut self) -> impl Iterator<Item = &E> {
+        let fresh = self.resimulating.is_none();
+        self.events.read().filter(move |_| fresh)
+    }
+}
+
 pub struct SchudulePlugin;
 
 impl Plugin for SchudulePlugin {
@@ -29,9 +55,35 @@ impl Plugin for SchudulePlugin {
                 InGameSet::CollisionReaction,
                 InGameSet::RenderSetup,
             )
-                .chain()
+                .chain(),
+        );
+        app.configure_sets(
+            Update,
+            (
+                InGameSet::DespawnEntities,
+                InGameSet::UpdateEntities,
+                InGameSet::CollisionDetection,
+                InGameSet::CollisionReaction,
+            )
                 .run_if(in_state(GameState::InGame)),
         );
+        // the ship select screen shows the ship, so loading and drawing carry on behind it
+        app.configure_sets(
+            Update,
+            (InGameSet::LoadEntities, InGameSet::RenderSetup)
+                .run_if(in_state(GameState::InGame).or(in_state(GameState::ShipSelect))),
+        );
+        // nothing can move until the playfield is known
+        app.configure_sets(
+            Update,
+            (
+                InGameSet::UpdateEntities,
+                InGameSet::CollisionDetection,
+                InGameSet::CollisionReaction,
+                InGameSet::RenderSetup,
+            )
+                .run_if(resource_exists::<Grid>),
+        );
         app.configure_sets(
             Update,
             InGameSet::GameInput
@@ -1,22 +1,125 @@
-use crate::{schedule::InGameSet, GameState};
+use crate::{bodies::*, console::{Cheated, ConfigExt, ConsoleExt}, grid::Grid, schedule::{resimulating, InGameSet}, ship::Pilot, GameState};
 use bevy::prelude::*;
-use bevy_common_assets::ron::RonAssetPlugin;
 use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
 
-// TODO! add teams to score
+// points by Collider team, and by the controller that earned them
 #[derive(Resource, Default)]
-struct Score {
-    score: u16,
+pub struct Score {
+    pub teams: BTreeMap<u32, u64>,
+    pub players: HashMap<Entity, u64>,
 }
 
-#[derive(Asset, Default, Deserialize, Clone, Copy, TypePath)]
-struct ScoreConfig {
+impl Score {
+    pub fn team(&self, team: u32) -> u64 {
+        self.teams.get(&team).copied().unwrap_or(0)
+    }
+
+    fn add(&mut self, team: u32, player: Option<Entity>, points: u64) {
+        let total = self.teams.entry(team).or_default();
+        *total = total.saturating_add(points);
+        if let Some(player) = player {
+            let total = self.players.entry(player).or_default();
+            *total = total.saturating_add(points);
+        }
+    }
+
+    // best first, ties to the lower team
+    pub fn ranking(&self) -> Vec<(u32, u64)> {
+        let mut ranked: Vec<(u32, u64)> = self.teams.iter().map(|(team, score)| (*team, *score)).collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+// one controller's kills in quick succession, the multiplier steps up every combo_kills of them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Streak {
+    pub kills: u32,
+    // when the last kill or decay step was
+    pub last: Duration,
+}
+
+impl Streak {
+    fn multiplier(&self, config: &ScoreConfig) -> u32 {
+        (1 + self.kills / config.combo_kills.max(1)).min(config.combo_max.max(1))
+    }
+}
+
+// by the controller that earned them, like Score.players
+#[derive(Resource, Default)]
+pub struct Combo {
+    pub players: HashMap<Entity, Streak>,
+}
+
+impl Combo {
+    // points nobody earned, a kill without a shooter, get no combo
+    fn multiplier(&self, player: Option<Entity>, config: &ScoreConfig) -> u32 {
+        player
+            .and_then(|player| self.players.get(&player))
+            .map_or(1, |streak| streak.multiplier(config))
+    }
+
+    fn best(&self, config: &ScoreConfig) -> u32 {
+        self.players
+            .values()
+            .map(|streak| streak.multiplier(config))
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+#[derive(Asset, Default, Deserialize, Clone, TypePath)]
+pub struct ScoreConfig {
     font_size: f32,
     margin: f32,
+    combo_window: u64,
+    combo_kills: u32,
+    combo_max: u32,
+    popup_size: f32,
+    popup_lifetime: u64,
+    popup_rise: f32,
+    extra_lives: Vec<u64>,
+    extra_life_every: u64,
+    // where the scores go for each count of teams, in halves of the playfield from the centre
+    layouts: Vec<Vec<(f32, f32)>>,
+    colors: HashMap<u32, (f32, f32, f32)>,
+}
+
+impl ScoreConfig {
+    // how many extra lives a score has earned in total
+    fn lives_at(&self, score: u64) -> usize {
+        let listed = self.extra_lives.iter().filter(|at| **at <= score).count();
+        let last = self.extra_lives.last().copied().unwrap_or(0);
+        if self.extra_life_every == 0 || score < last {
+            return listed;
+        }
+        listed + ((score - last) / self.extra_life_every) as usize
+    }
+
+    fn slot(&self, index: usize, count: usize) -> Vec2 {
+        let laid_out = count
+            .checked_sub(1)
+            .and_then(|layout| self.layouts.get(layout))
+            .and_then(|layout| layout.get(index));
+        match laid_out {
+            Some((x, y)) => Vec2::new(*x, *y),
+            // more teams than there are layouts for, spread them along the top
+            None => Vec2::new((index as f32 + 0.5) / count as f32 * 2.0 - 1.0, 1.0),
+        }
+    }
+
+    // teams without a colour are white
+    fn color(&self, team: u32) -> Color {
+        self.colors
+            .get(&team)
+            .map_or(Color::WHITE, |color| Color::srgb(color.0, color.1, color.2))
+    }
 }
 
 #[derive(Resource)]
-struct ScoreConfigHandle {
+pub struct ScoreConfigHandle {
     config: Handle<ScoreConfig>,
 }
 
@@ -30,73 +133,290 @@ fn load_config(
     });
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreSource {
+    Asteroid,
+    // cleared all at once, worth the points but no combo
+    SmartBomb,
+}
+
+// points are before the combo multiplier, player is the controller whose bullet or ship it was
 #[derive(Event)]
-pub struct Scored;
+pub struct Scored {
+    pub points: u32,
+    // the asteroid's size class, see AsteroidConfig
+    pub size: Option<usize>,
+    pub position: Vec2,
+    pub source: ScoreSource,
+    pub team: u32,
+    pub player: Option<Entity>,
+}
+
+#[derive(Event)]
+pub struct ExtraLifeEarned {
+    pub team: u32,
+}
+
+// floats up from the kill showing what it was worth
+#[derive(Component)]
+pub struct ScorePopup;
 
-fn update_score(mut score: ResMut<Score>, mut events: EventReader<Scored>) {
-    for _ in events.read() {
-        score.score += 1;
+#[allow(clippy::too_many_arguments)]
+pub fn update_score(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+    mut events: EventReader<Scored>,
+    mut extra_lives: EventWriter<ExtraLifeEarned>,
+    configs: Res<Assets<ScoreConfig>>,
+    config_handle: Res<ScoreConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    for event in events.read() {
+        if let (ScoreSource::Asteroid, Some(player)) = (event.source, event.player) {
+            let streak = combo.players.entry(player).or_default();
+            streak.kills += 1;
+            streak.last = time.elapsed();
+        }
+        let points = event.points * combo.multiplier(event.player, config);
+        let earned = config.lives_at(score.team(event.team));
+        score.add(event.team, event.player, points as u64);
+        for _ in earned..config.lives_at(score.team(event.team)) {
+            extra_lives.send(ExtraLifeEarned { team: event.team });
+        }
+
+        commands.spawn((
+            ScorePopup,
+            Text2d::new(format!("+{}", points)),
+            TextFont {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: config.popup_size,
+                ..default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+            TextColor(config.color(event.team)),
+            Position(event.position),
+            Velocity(Vec2::new(0.0, config.popup_rise)),
+            Rotation(0.0),
+            Scale(1.0),
+            TimeStamp(time.elapsed()),
+            Transform::default(),
+        ));
     }
 }
 
-fn reset_score(mut score: ResMut<Score>) {
-    score.score = 0;
+// the multiplier drops a step for every window without a kill
+fn decay_combo(
+    time: Res<Time>,
+    mut combo: ResMut<Combo>,
+    configs: Res<Assets<ScoreConfig>>,
+    config_handle: Res<ScoreConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let window = Duration::from_millis(config.combo_window);
+    for streak in combo.players.values_mut() {
+        if time.elapsed().saturating_sub(streak.last) > window {
+            streak.kills = streak.kills.saturating_sub(config.combo_kills.max(1));
+            streak.last += window;
+        }
+    }
+    combo.players.retain(|_, streak| streak.kills > 0);
 }
 
+fn fade_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popups: Query<(Entity, &TimeStamp, &mut TextColor), With<ScorePopup>>,
+    configs: Res<Assets<ScoreConfig>>,
+    config_handle: Res<ScoreConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let lifetime = Duration::from_millis(config.popup_lifetime);
+    for (entity, spawn_time, mut color) in popups.iter_mut() {
+        // a netplay rollback winds the clock back
+        let age = time.elapsed().saturating_sub(spawn_time.0);
+        if age > lifetime {
+            commands.entity(entity).despawn();
+        } else {
+            color.0.set_alpha(1.0 - age.as_secs_f32() / lifetime.as_secs_f32());
+        }
+    }
+}
+
+fn reset_score(mut score: ResMut<Score>, mut combo: ResMut<Combo>) {
+    *score = Score::default();
+    *combo = Combo::default();
+}
+
+fn despawn_popups(mut commands: Commands, popups: Query<Entity, With<ScorePopup>>) {
+    for entity in popups.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+// one for every team flying or with points
 #[derive(Component)]
-struct PlayerScore;
+struct TeamScore(u32);
 
+// everyone's final score, shown until the restart
 #[derive(Component)]
-struct NeedsScoreboard;
+struct Ranking;
 
-fn spawn_playerscore (
+#[allow(clippy::too_many_arguments)]
+fn update_scoreboard(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    grid: Res<Grid>,
+    score: Res<Score>,
+    combo: Res<Combo>,
+    pilots: Query<&Pilot>,
+    configs: Res<Assets<ScoreConfig>>,
+    config_handle: Res<ScoreConfigHandle>,
+    mut boards: Query<(Entity, &TeamScore, &mut Text2d, &mut TextColor, &mut Transform)>,
 ) {
-    commands.spawn((PlayerScore, NeedsScoreboard));
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let teams: BTreeSet<u32> = pilots
+        .iter()
+        .map(|pilot| pilot.team)
+        .chain(score.teams.keys().copied())
+        .collect();
+
+    let mut shown = BTreeSet::new();
+    for (entity, board, mut text, mut color, mut transform) in boards.iter_mut() {
+        let Some(index) = teams.iter().position(|team| *team == board.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        shown.insert(board.0);
+
+        let points = score.team(board.0);
+        let shown_text = match (teams.len(), combo.best(config)) {
+            (1, 1) => points.to_string(),
+            // there's only room for one combo, so it's only shown next to a single score
+            (1, multiplier) => format!("{}  x{}", points, multiplier),
+            _ => format!("P{} {}", board.0, points),
+        };
+        if text.0 != shown_text {
+            text.0 = shown_text;
+        }
+        color.0 = config.color(board.0);
+
+        // the playfield can change size, so this follows it every frame
+        let slot = config.slot(index, teams.len());
+        transform.translation = Vec3::new(
+            slot.x * grid.width_half * grid.size,
+            slot.y * (grid.height_half * grid.size - config.margin),
+            0.0,
+        );
+    }
+
+    // new teams show up next frame, once they're placed
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    for team in teams.difference(&shown) {
+        commands.spawn((
+            TeamScore(*team),
+            Text2d::new(""),
+            TextFont {
+                font: font.clone(),
+                font_size: config.font_size,
+                ..default()
+            },
+            TextColor(config.color(*team)),
+            TextLayout::new_with_justify(JustifyText::Center),
+            Transform::default(),
+        ));
+    }
 }
 
-fn spawn_scoreboard(
+fn spawn_ranking(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    window: Query<&Window>,
+    score: Res<Score>,
+    pilots: Query<(Entity, &Pilot)>,
     configs: Res<Assets<ScoreConfig>>,
     config_handle: Res<ScoreConfigHandle>,
-    mut scoreboards: Query<(Entity, &mut NeedsScoreboard)>,
 ) {
-    // only spawn once...
-    if let Ok(window) = window.get_single() {
-        if let Some(config) = configs.get(config_handle.config.id()) {
-            for (entity, _) in scoreboards.iter_mut() {
-                let window_height = window.resolution.height();
-                let text_height = window_height / 2.0 - config.margin;
-
-                let font = asset_server.load("fonts/FiraMono-Medium.ttf");
-                let text_font = TextFont {
-                    font,
-                    font_size: config.font_size,
-                    ..default()
-                };
-
-                //needs to append, not spawn
-                commands.entity(entity).insert((
-                    Text2d::new("0"),
-                    text_font.clone(),
-                    TextLayout::new_with_justify(JustifyText::Center),
-                    Transform::from_translation(Vec3::new(0.0, text_height, 0.0)),
-                ));
-
-                //remove scoreboard
-                commands.entity(entity).remove::<NeedsScoreboard>();
+    let ranked = score.ranking();
+    let mut ranking = match ranked.as_slice() {
+        [] => return,
+        [(_, points)] => format!("Final score {}\n", points),
+        _ => "Final ranking\n".to_string(),
+    };
+    if ranked.len() > 1 {
+        for (place, (team, points)) in ranked.iter().enumerate() {
+            ranking.push_str(&format!("{}. P{}  {}\n", place + 1, team, points));
+            // only worth splitting up when the team had more than one pilot
+            let players: Vec<Entity> = pilots
+                .iter()
+                .filter(|(_, pilot)| pilot.team == *team)
+                .map(|(entity, _)| entity)
+                .collect();
+            if players.len() > 1 {
+                for (index, player) in players.iter().enumerate() {
+                    let points = score.players.get(player).copied().unwrap_or(0);
+                    ranking.push_str(&format!("     P{}.{}  {}\n", team, index + 1, points));
+                }
             }
         }
     }
+
+    let font_size = configs
+        .get(config_handle.config.id())
+        .map_or(36.0, |config| config.font_size);
+    commands.spawn((
+        Ranking,
+        Text2d::new(ranking),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_translation(Vec3::new(0.0, 0.0, 1.0)),
+    ));
+}
+
+fn despawn_ranking(mut commands: Commands, rankings: Query<Entity, With<Ranking>>) {
+    for entity in rankings.iter() {
+        commands.entity(entity).despawn();
+    }
 }
 
-fn update_scoreboard(mut player_score: Query<&mut Text2d, With<PlayerScore>>, score: Res<Score>) {
-    if score.is_changed() {
-        if let Ok(mut player_score) = player_score.get_single_mut() {
-            player_score.0 = score.score.to_string();
+fn score_command(
+    In(args): In<Vec<String>>,
+    mut score: ResMut<Score>,
+    pilots: Query<(Entity, &Pilot)>,
+    mut cheated: ResMut<Cheated>,
+) -> String {
+    let team = args.get(1).and_then(|team| team.parse::<u32>().ok()).unwrap_or(1);
+    match args.first().and_then(|points| points.parse::<u64>().ok()) {
+        Some(points) => {
+            let before = score.team(team);
+            score.teams.insert(team, points);
+            // the difference goes to the team's first pilot, so the players still add up to the team
+            let first = pilots
+                .iter()
+                .filter(|(_, pilot)| pilot.team == team)
+                .map(|(player, _)| player)
+                .min();
+            if let Some(player) = first {
+                let total = score.players.entry(player).or_default();
+                *total = total.saturating_add(points).saturating_sub(before);
+            }
+            cheated.0 = true;
+            format!("team {} score set to {}", team, points)
         }
+        None => format!("team {} score is {}, score <n> [team]", team, score.team(team)),
     }
 }
 
@@ -104,15 +424,25 @@ pub struct ScorePlugin;
 
 impl Plugin for ScorePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RonAssetPlugin::<ScoreConfig>::new(&["score.ron"]));
+        app.add_config::<ScoreConfig>(&["score.ron"]);
         app.init_resource::<Score>();
+        app.init_resource::<Combo>();
         app.add_event::<Scored>();
-        app.add_systems(Startup, (load_config, spawn_playerscore));
-        app.add_systems(Update, (spawn_scoreboard).in_set(InGameSet::LoadEntities));
+        app.add_event::<ExtraLifeEarned>();
+        app.add_systems(Startup, load_config);
+        // scored along with the despawns that earned it, so snapshots never have points in flight
+        app.add_systems(Update, (update_score).in_set(InGameSet::DespawnEntities));
+        app.add_systems(
+            Update,
+            (decay_combo, fade_popups.run_if(not(resimulating))).in_set(InGameSet::UpdateEntities),
+        );
         app.add_systems(
             Update,
-            (update_score, update_scoreboard).in_set(InGameSet::UpdateEntities),
+            (update_scoreboard).in_set(InGameSet::RenderSetup),
         );
-        app.add_systems(OnEnter(GameState::GameOver), reset_score);
+        app.add_systems(OnEnter(GameState::GameOver), (spawn_ranking, despawn_popups));
+        // scores stay up over the ranking, they're only cleared for the next run
+        app.add_systems(OnExit(GameState::GameOver), (reset_score, despawn_ranking));
+        app.add_console_command("score", "score <n> [team]", score_command);
     }
 }
@@ -0,0 +1,286 @@
+use crate::asteroid::{Asteroid, MeshVariant};
+use crate::bodies::*;
+use crate::bullet::Bullet;
+use crate::control::NetController;
+use crate::input_actions::*;
+use crate::replication::*;
+use crate::schedule::InGameSet;
+use crate::score::Score;
+use crate::ship::{Pilot, ShipKind};
+use crate::GameState;
+use bevy::prelude::*;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::{Message, WebSocket};
+
+#[derive(Resource, Clone, Debug)]
+pub struct ServerSettings {
+    pub bind: SocketAddr,
+    // ships waiting for a player, anyone joining after they're taken spectates
+    pub players: u32,
+    // frames between snapshots
+    pub send_interval: u32,
+}
+
+type Handshake = MidHandshake<ServerHandshake<TcpStream, NoCallback>>;
+
+type ReplicatedBody<'a> = (
+    Entity,
+    &'a Position,
+    &'a Rotation,
+    &'a Velocity,
+    &'a Scale,
+    Option<&'a MeshVariant>,
+    Option<&'a Collider>,
+    Has<Asteroid>,
+    Option<&'a ShipKind>,
+    Has<Bullet>,
+);
+
+struct Client {
+    socket: WebSocket<TcpStream>,
+    joined: bool,
+    pilot: Option<usize>,
+    inputs: Vec<Input>,
+    // what this client was last sent, deltas are taken against it
+    view: WorldView,
+    closed: bool,
+}
+
+#[derive(Resource)]
+struct Server {
+    listener: TcpListener,
+    handshakes: Vec<Handshake>,
+    clients: Vec<Client>,
+    pilots: Vec<Entity>,
+    send_interval: u32,
+    tick: u32,
+}
+
+impl Client {
+    fn send(&mut self, message: &ServerMessage) {
+        let Ok(text) = ron::to_string(message) else {
+            return;
+        };
+        match self.socket.send(Message::Text(text)) {
+            Ok(()) => (),
+            // still queued, the next send flushes it
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => (),
+            Err(_) => self.closed = true,
+        }
+    }
+}
+
+fn setup_server(mut commands: Commands, settings: Res<ServerSettings>) {
+    // nobody can join without the socket, say why and stop rather than unwind
+    let listener = match TcpListener::bind(settings.bind).and_then(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("could not serve on {}: {}", settings.bind, e);
+            std::process::exit(1);
+        }
+    };
+    info!("serving on ws://{}", settings.bind);
+
+    // spread the ships out along the middle of the playfield
+    let spacing = 4.0;
+    let offset = (settings.players as f32 - 1.0) * spacing * 0.5;
+    let pilots = (0..settings.players)
+        .map(|id| {
+            commands
+                .spawn((
+                    NetController { id },
+                    Pilot {
+                        team: id + 1,
+                        spawn: Vec2::new(id as f32 * spacing - offset, 0.0),
+                        ..default()
+                    },
+                ))
+                .id()
+        })
+        .collect();
+
+    commands.insert_resource(Server {
+        listener,
+        handshakes: Vec::new(),
+        clients: Vec::new(),
+        pilots,
+        send_interval: settings.send_interval.max(1),
+        tick: 0,
+    });
+}
+
+fn new_client(socket: WebSocket<TcpStream>) -> Client {
+    Client {
+        socket,
+        joined: false,
+        pilot: None,
+        inputs: Vec::new(),
+        view: WorldView::new(),
+        closed: false,
+    }
+}
+
+fn accept_clients(mut server: ResMut<Server>) {
+    loop {
+        match server.listener.accept() {
+            Ok((stream, address)) => {
+                info!("connection from {}", address);
+                if stream.set_nonblocking(true).is_err() {
+                    continue;
+                }
+                match tungstenite::accept(stream) {
+                    Ok(socket) => server.clients.push(new_client(socket)),
+                    Err(HandshakeError::Interrupted(handshake)) => server.handshakes.push(handshake),
+                    Err(HandshakeError::Failure(e)) => warn!("handshake failed: {}", e),
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("accept failed: {}", e);
+                break;
+            }
+        }
+    }
+
+    // the handshake is nonblocking too, pick up where it left off
+    for handshake in std::mem::take(&mut server.handshakes) {
+        match handshake.handshake() {
+            Ok(socket) => server.clients.push(new_client(socket)),
+            Err(HandshakeError::Interrupted(handshake)) => server.handshakes.push(handshake),
+            Err(HandshakeError::Failure(e)) => warn!("handshake failed: {}", e),
+        }
+    }
+}
+
+fn read_clients(mut server: ResMut<Server>, mut writer: EventWriter<InputEvent>) {
+    let server = &mut *server;
+    let mut joins = Vec::new();
+    for (index, client) in server.clients.iter_mut().enumerate() {
+        loop {
+            match client.socket.read() {
+                Ok(Message::Text(text)) => match ron::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Join { spectate }) if !client.joined => {
+                        client.joined = true;
+                        joins.push((index, spectate));
+                    }
+                    Ok(ClientMessage::Input(inputs)) => {
+                        client.inputs = inputs
+                            .into_iter()
+                            .filter(|input| remote_action(input.action))
+                            .collect();
+                    }
+                    Ok(_) => (),
+                    Err(e) => warn!("bad message from client: {}", e),
+                },
+                Ok(Message::Close(_)) => {
+                    client.closed = true;
+                    break;
+                }
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    client.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    // hand out free ships to players who just joined
+    for (index, spectate) in joins {
+        let taken: Vec<usize> = server.clients.iter().filter_map(|client| client.pilot).collect();
+        let free = (0..server.pilots.len()).find(|pilot| !taken.contains(pilot));
+        let client = &mut server.clients[index];
+        client.pilot = if spectate { None } else { free };
+        client.send(&ServerMessage::Welcome {
+            pilot: client.pilot.map(|pilot| pilot as u32),
+        });
+    }
+    server.clients.retain(|client| !client.closed);
+
+    for client in server.clients.iter() {
+        let Some(pilot) = client.pilot else {
+            continue;
+        };
+        for input in client.inputs.iter() {
+            writer.send(InputEvent {
+                controller: server.pilots[pilot],
+                input: *input,
+            });
+        }
+    }
+}
+
+fn broadcast(
+    mut server: ResMut<Server>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    bodies: Query<ReplicatedBody>,
+) {
+    server.tick += 1;
+    if !server.tick.is_multiple_of(server.send_interval) {
+        return;
+    }
+
+    let mut view = WorldView::new();
+    for (entity, position, rotation, velocity, scale, variant, collider, asteroid, ship, bullet) in
+        bodies.iter()
+    {
+        let team = collider.map_or(0, |collider| collider.team);
+        let kind = if asteroid {
+            Kind::Asteroid {
+                variant: variant.map_or(0, |variant| variant.0),
+            }
+        } else if let Some(ship) = ship {
+            Kind::Ship { team, ship: ship.0 }
+        } else if bullet {
+            Kind::Bullet { team }
+        } else {
+            // particles and the like are left to each client
+            continue;
+        };
+        view.insert(
+            entity.to_bits(),
+            Replica {
+                kind,
+                position: position.0,
+                rotation: rotation.0,
+                velocity: velocity.0,
+                scale: scale.0,
+            },
+        );
+    }
+
+    let tick = server.tick;
+    for client in server.clients.iter_mut().filter(|client| client.joined) {
+        let (changed, removed) = diff(&client.view, &view);
+        client.send(&ServerMessage::Frame(FrameDelta {
+            tick,
+            state: *state.get(),
+            scores: score.teams.clone(),
+            changed,
+            removed,
+        }));
+        client.view = view.clone();
+    }
+}
+
+pub struct ServerPlugin {
+    pub settings: ServerSettings,
+}
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone());
+        app.add_systems(PreStartup, setup_server);
+        app.add_systems(PreUpdate, accept_clients);
+        app.add_systems(Update, (read_clients).in_set(InGameSet::GameInput));
+        app.add_systems(Last, broadcast);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::input::ControlScheme;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// anything not given on the command line comes from here, next to the savegame
+const SETTINGS_PATH: &str = "settings.ron";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Seed {
+    Fixed(u64),
+    // a new world every run, the seed used is logged so it can be played again
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScreenMode {
+    Windowed { width: f32, height: f32 },
+    Fullscreen,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub seed: Seed,
+    // laid out like assets, whatever it has wins over assets and any mods
+    pub assets: Option<PathBuf>,
+    pub screen: ScreenMode,
+    pub headless: bool,
+    // how the local player's gamepad flies, the console can switch it per player
+    pub scheme: ControlScheme,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    // settings are read before logging is up, anything wrong is kept for report
+    #[serde(skip)]
+    warnings: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            seed: Seed::Fixed(crate::WORLD_SEED),
+            assets: None,
+            screen: ScreenMode::Windowed {
+                width: 1280.0,
+                height: 720.0,
+            },
+            headless: false,
+            scheme: ControlScheme::Classic,
+            record: None,
+            replay: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    // the settings file, then whatever the command line overrides
+    //   asteroids --seed random --assets mods/hard --windowed 1920x1080 --twin-stick --record run.ron
+    pub fn load() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let mut settings = Self::from_file(Path::new(SETTINGS_PATH));
+        settings.apply_args(&args);
+        settings
+    }
+
+    fn from_file(path: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        ron::from_str(&text).unwrap_or_else(|e| Self {
+            warnings: vec![format!("ignoring {}: {}", path.display(), e)],
+            ..default()
+        })
+    }
+
+    // once the log plugin is in
+    pub fn report(&self) {
+        for warning in self.warnings.iter() {
+            warn!("{}", warning);
+        }
+    }
+
+    pub fn apply_args(&mut self, args: &[String]) {
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+        };
+
+        // a typo keeps whatever was there before rather than ending the game
+        if let Some(seed) = value("--seed") {
+            match seed.as_str() {
+                "random" => self.seed = Seed::Random,
+                seed => match seed.parse() {
+                    Ok(seed) => self.seed = Seed::Fixed(seed),
+                    Err(_) => self
+                        .warnings
+                        .push(format!("--seed takes a number or random, not {}", seed)),
+                },
+            }
+        }
+        if let Some(assets) = value("--assets") {
+            self.assets = Some(PathBuf::from(assets));
+        }
+        if args.iter().any(|arg| arg == "--fullscreen") {
+            self.screen = ScreenMode::Fullscreen;
+        }
+        if let Some(size) = value("--windowed") {
+            let parsed = size
+                .split_once('x')
+                .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+            match parsed {
+                Some((width, height)) => self.screen = ScreenMode::Windowed { width, height },
+                None => self
+                    .warnings
+                    .push(format!("--windowed takes a size like 1280x720, not {}", size)),
+            }
+        }
+        if args.iter().any(|arg| arg == "--headless") {
+            self.headless = true;
+        }
+        if args.iter().any(|arg| arg == "--twin-stick") {
+            self.scheme = ControlScheme::TwinStick;
+        }
+        if let Some(record) = value("--record") {
+            self.record = Some(PathBuf::from(record));
+        }
+        if let Some(replay) = value("--replay") {
+            self.replay = Some(PathBuf::from(replay));
+        }
+    }
+
+    // a random seed is picked here, once, so a recording knows what it was
+    pub fn world_seed(&self) -> u64 {
+        match self.seed {
+            Seed::Fixed(seed) => seed,
+            Seed::Random => {
+                let seed = random_seed();
+                info!("world seed {}", seed);
+                seed
+            }
+        }
+    }
+
+    pub fn window(&self) -> Window {
+        let mut window = Window {
+            fit_canvas_to_parent: true,
+            ..default()
+        };
+        match self.screen {
+            ScreenMode::Windowed { width, height } => window.resolution = (width, height).into(),
+            ScreenMode::Fullscreen => {
+                window.mode = bevy::window::WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+            }
+        }
+        window
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn random_seed() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(std::time::SystemTime::now())
+}
+
+// the browser has no clock std can read, but the page has been open a different while every time
+#[cfg(target_arch = "wasm32")]
+fn random_seed() -> u64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map_or(crate::WORLD_SEED, |performance| (performance.now() * 1000.0) as u64)
+}
@@ -0,0 +1,373 @@
+use crate::asteroid::Asteroid;
+use crate::bodies::*;
+use crate::control::{Pawn, PlayerController, ShipPawn};
+use crate::grid::Grid;
+use crate::input_actions::*;
+use crate::powerup::Shielded;
+use crate::schedule::InGameSet;
+use crate::ship::{Ship, ShipConfig, ShipConfigHandle, ShipKind};
+use crate::GameState;
+use bevy::prelude::*;
+
+// the energy bar, in pixels
+const BAR_SIZE: Vec2 = Vec2::new(200.0, 8.0);
+const BAR_MARGIN: f32 = 20.0;
+// a drained shield only comes back up once this much of the pool has recharged
+const RERAISE_FRACTION: f32 = 0.25;
+
+// the pool a ship's shield runs from, sized by its ShipDef
+#[derive(Component)]
+pub struct Shield {
+    pub energy: f32,
+    pub raised: bool,
+}
+
+// drawn around its ship while the shield is up
+#[derive(Component)]
+struct ShieldBubble {
+    ship: Entity,
+}
+
+#[derive(Component)]
+struct ShieldBar;
+
+#[derive(Component)]
+struct ShieldBarFill;
+
+#[derive(Resource)]
+struct ShieldAssets {
+    bubble_mesh: Handle<Mesh>,
+    bubble_material: Handle<ColorMaterial>,
+}
+
+fn load_shield(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    shield_assets: Option<Res<ShieldAssets>>,
+) {
+    if shield_assets.is_some() {
+        return;
+    }
+    // a unit ring, scaled to each ship's shield radius
+    let bubble_mesh = meshes.add(Annulus::new(0.9, 1.0));
+    let bubble_material = materials.add(Color::srgba(0.3, 0.8, 1.0, 0.5));
+
+    let bar_mesh = meshes.add(Rectangle::new(1.0, 1.0));
+    commands.spawn((
+        ShieldBar,
+        Mesh2d(bar_mesh.clone()),
+        MeshMaterial2d(materials.add(Color::srgba(1.0, 1.0, 1.0, 0.2))),
+        Transform::from_scale(BAR_SIZE.extend(1.0)),
+        Visibility::Hidden,
+    ));
+    commands.spawn((
+        ShieldBar,
+        ShieldBarFill,
+        Mesh2d(bar_mesh),
+        MeshMaterial2d(materials.add(Color::srgb(0.3, 0.8, 1.0))),
+        Transform::from_scale(BAR_SIZE.extend(1.0)),
+        Visibility::Hidden,
+    ));
+
+    commands.insert_resource(ShieldAssets {
+        bubble_mesh,
+        bubble_material,
+    });
+}
+
+type UnshieldedShip = (With<Ship>, Without<Shield>);
+
+fn bubble(ship: Entity, assets: &ShieldAssets) -> impl Bundle {
+    (
+        ShieldBubble { ship },
+        Position(Vec2::ZERO),
+        Rotation(0.0),
+        Scale(1.0),
+        Mesh2d(assets.bubble_mesh.clone()),
+        MeshMaterial2d(assets.bubble_material.clone()),
+        Transform::default(),
+        Visibility::Hidden,
+    )
+}
+
+// new ships start with a full pool, however they were spawned
+fn fit_shields(
+    mut commands: Commands,
+    ships: Query<(Entity, &ShipKind), UnshieldedShip>,
+    shield_assets: Option<Res<ShieldAssets>>,
+    configs: Res<Assets<ShipConfig>>,
+    config_handle: Res<ShipConfigHandle>,
+) {
+    let (Some(assets), Some(config)) = (shield_assets, configs.get(config_handle.config.id())) else {
+        return;
+    };
+    for (entity, kind) in ships.iter() {
+        let Some(ship) = config.ship(kind.0) else {
+            continue;
+        };
+        commands.entity(entity).insert(Shield {
+            energy: ship.shield_energy,
+            raised: false,
+        });
+        commands.spawn(bubble(entity, &assets));
+    }
+}
+
+// a saved or rolled back ship keeps the pool it had rather than a full one
+pub fn restore_shield(world: &mut World, ship: Entity, energy: f32, raised: bool) {
+    // without the assets fit_shields gives it a full pool once they're in
+    let Some(assets) = world.get_resource::<ShieldAssets>() else {
+        return;
+    };
+    world.spawn(bubble(ship, assets));
+    let mut ship = world.entity_mut(ship);
+    ship.insert(Shield { energy, raised });
+    if raised {
+        ship.insert(Deflecting);
+    }
+}
+
+// an emptied shield would flicker up every time a sliver recharged, so it has to refill a bit first
+fn stays_raised(held: bool, was_raised: bool, energy: f32, pool: f32) -> bool {
+    if !held {
+        return false;
+    }
+    if was_raised {
+        energy > 0.0
+    } else {
+        energy > 0.0 && energy >= pool * RERAISE_FRACTION
+    }
+}
+
+fn raise_shields(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut ships: Query<(Entity, &mut Shield, &mut RigidBody, &ShipPawn, &ShipKind), With<Ship>>,
+    mut events: EventReader<InputEvent>,
+    configs: Res<Assets<ShipConfig>>,
+    config_handle: Res<ShipConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    // held, so it comes in every frame the key is down
+    let held: Vec<Entity> = events
+        .read()
+        .filter(|event| event.input.action == Actions::Shield && event.input.value > 0.0)
+        .map(|event| event.controller)
+        .collect();
+
+    for (entity, mut shield, mut body, pawn, kind) in ships.iter_mut() {
+        let Some(ship) = config.ship(kind.0) else {
+            continue;
+        };
+        let delta = time.delta_secs();
+        let held = held.contains(pawn.get_controller());
+        let raised = stays_raised(held, shield.raised, shield.energy, ship.shield_energy);
+        if raised {
+            shield.energy = (shield.energy - ship.shield_drain * delta).max(0.0);
+        } else {
+            shield.energy = (shield.energy + ship.shield_recharge * delta).min(ship.shield_energy);
+        }
+
+        if raised != shield.raised {
+            if raised {
+                commands.entity(entity).insert(Deflecting);
+            } else {
+                commands.entity(entity).remove::<Deflecting>();
+            }
+        }
+        shield.raised = raised;
+        // rocks meet the bubble, not the hull
+        body.radius = if raised { ship.shield_radius } else { ship.radius };
+    }
+}
+
+type ShieldedShip<'a> = (&'a mut Shield, &'a mut Velocity, &'a RigidBody, &'a Collider, &'a ShipKind);
+type DeflectedRock<'a> = (&'a mut Position, &'a mut Velocity, &'a RigidBody);
+
+// a raised shield pays for every hit and knocks rocks away instead of breaking them
+fn absorb_hits(
+    mut ships: Query<ShieldedShip, (With<Ship>, Without<Asteroid>)>,
+    mut asteroids: Query<DeflectedRock, (With<Asteroid>, Without<Ship>)>,
+    colliders: Query<&Collider>,
+    mut collisions: EventReader<Collision>,
+    configs: Res<Assets<ShipConfig>>,
+    config_handle: Res<ShipConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    for event in collisions.read() {
+        for (entity_a, entity_b, dir) in [
+            (event.entity1, event.entity2, event.dir),
+            (event.entity2, event.entity1, -event.dir),
+        ] {
+            let Ok((mut shield, mut ship_vel, ship_body, ship_collider, kind)) = ships.get_mut(entity_a)
+            else {
+                continue;
+            };
+            let Ok(collider) = colliders.get(entity_b) else {
+                continue;
+            };
+            if !shield.raised || collider.team == ship_collider.team || collider.team == PICKUP_TEAM {
+                continue;
+            }
+            if let Some(ship) = config.ship(kind.0) {
+                shield.energy = (shield.energy - ship.shield_hit).max(0.0);
+            }
+
+            if let Ok((mut ast_pos, mut ast_vel, ast_body)) = asteroids.get_mut(entity_b) {
+                // centres on top of each other, any way out will do
+                let normal = dir.try_normalize().unwrap_or(Vec2::Y);
+                (ship_vel.0, ast_vel.0) = collision_bounce(
+                    ship_vel.0,
+                    ast_vel.0,
+                    normal,
+                    ship_body.mass,
+                    ast_body.mass,
+                );
+                // push the rock out of the bubble so it isn't hit again next frame
+                ast_pos.0 += normal * (event.collide_dist - event.dist);
+            }
+        }
+    }
+}
+
+type BubbleShip<'a> = (&'a Position, &'a Shield, &'a ShipKind, Has<Shielded>);
+
+fn follow_ships(
+    mut commands: Commands,
+    mut bubbles: Query<(Entity, &ShieldBubble, &mut Position, &mut Scale, &mut Visibility), Without<Ship>>,
+    ships: Query<BubbleShip, With<Ship>>,
+    grid: Res<Grid>,
+    configs: Res<Assets<ShipConfig>>,
+    config_handle: Res<ShipConfigHandle>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    for (entity, bubble, mut position, mut scale, mut visibility) in bubbles.iter_mut() {
+        let Ok((ship_position, shield, kind, shielded)) = ships.get(bubble.ship) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        position.0 = ship_position.0;
+        if let Some(ship) = config.ship(kind.0) {
+            scale.0 = ship.shield_radius * grid.size;
+        }
+        // a shield pickup shows the same bubble
+        *visibility = if shield.raised || shielded {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+type BarShip<'a> = (&'a Shield, &'a ShipPawn, &'a ShipKind);
+
+// the local player's pool along the bottom edge, or the first ship's when nobody local is flying
+fn update_bar(
+    grid: Res<Grid>,
+    ships: Query<BarShip, With<Ship>>,
+    players: Query<(), With<PlayerController>>,
+    mut bars: Query<(&mut Transform, &mut Visibility, Has<ShieldBarFill>), With<ShieldBar>>,
+    configs: Res<Assets<ShipConfig>>,
+    config_handle: Res<ShipConfigHandle>,
+) {
+    let shown = ships
+        .iter()
+        .find(|(_, pawn, _)| players.contains(*pawn.get_controller()))
+        .or_else(|| ships.iter().next());
+    let fraction = shown.and_then(|(shield, _, kind)| {
+        let config = configs.get(config_handle.config.id())?;
+        let ship = config.ship(kind.0)?;
+        Some((shield.energy / ship.shield_energy.max(f32::EPSILON)).clamp(0.0, 1.0))
+    });
+
+    let y = -grid.height_half * grid.size + BAR_MARGIN;
+    for (mut transform, mut visibility, fill) in bars.iter_mut() {
+        let Some(fraction) = fraction else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Visible;
+        if fill {
+            // drains toward the left end
+            transform.scale.x = BAR_SIZE.x * fraction;
+            transform.translation = Vec3::new(-BAR_SIZE.x * (1.0 - fraction) / 2.0, y, 2.0);
+        } else {
+            transform.translation = Vec3::new(0.0, y, 1.0);
+        }
+    }
+}
+
+fn despawn_bubbles(mut commands: Commands, bubbles: Query<Entity, With<ShieldBubble>>) {
+    for entity in bubbles.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub struct ShieldPlugin;
+
+impl Plugin for ShieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (load_shield, fit_shields).chain().in_set(InGameSet::LoadEntities),
+        );
+        app.add_systems(Update, (raise_shields).in_set(InGameSet::UpdateEntities));
+        app.add_systems(
+            Update,
+            (absorb_hits, follow_ships).chain().in_set(InGameSet::CollisionReaction),
+        );
+        app.add_systems(Update, (update_bar).in_set(InGameSet::RenderSetup));
+        app.add_systems(OnEnter(GameState::GameOver), despawn_bubbles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_shield_waits_to_recharge() {
+        let pool = 100.0;
+        // drained while held, it drops and stays down on a sliver of energy
+        assert!(!stays_raised(true, true, 0.0, pool));
+        assert!(!stays_raised(true, false, 1.0, pool));
+        assert!(!stays_raised(true, false, pool * RERAISE_FRACTION - 0.1, pool));
+        assert!(stays_raised(true, false, pool * RERAISE_FRACTION, pool));
+    }
+
+    #[test]
+    fn raised_shield_runs_dry() {
+        let pool = 100.0;
+        assert!(stays_raised(true, true, 1.0, pool));
+        assert!(!stays_raised(false, true, pool, pool));
+    }
+
+    #[test]
+    fn held_key_doesnt_flicker() {
+        // frames at 60fps with the key held throughout, drain and recharge as in a.ship.ron
+        let (pool, drain, recharge, delta) = (100.0, 30.0, 20.0, 1.0 / 60.0);
+        let (mut energy, mut raised) = (0.0_f32, false);
+        let mut changes = 0;
+        for _ in 0..600 {
+            let now = stays_raised(true, raised, energy, pool);
+            if now != raised {
+                changes += 1;
+            }
+            raised = now;
+            energy = if raised {
+                (energy - drain * delta).max(0.0)
+            } else {
+                (energy + recharge * delta).min(pool)
+            };
+        }
+        // up after 1.25s, empty 0.83s later and round again, rather than a change every frame
+        assert!(changes < 20, "{} changes", changes);
+    }
+}
@@ -1,38 +1,74 @@
 use crate::{
     bodies::*,
+    console::{Cheated, ConfigExt, ConsoleExt},
     bullet::CreateBullet,
     control::{Pawn, PlayerController, ShipPawn},
     input_actions::*,
+    powerup::{shield, ExtraLives, RapidFire, Shielded, SpreadShot},
+    save::BodyState,
     schedule::InGameSet,
     GameState,
 };
 use bevy::prelude::*;
-use bevy_common_assets::ron::RonAssetPlugin;
 use serde::Deserialize;
 use std::time::Duration;
 
-#[derive(Deserialize, Asset, Clone, TypePath)]
-struct ShipConfig {
-    speed: f32,
+// one ship in the roster, picked on the ship select screen
+#[derive(Deserialize, Clone, Debug)]
+pub struct ShipDef {
+    pub name: String,
+    pub speed: f32,
     damping: f32,
-    speed_angular: f32,
+    pub speed_angular: f32,
     damping_angular: f32,
+    pub radius: f32,
     mesh_path: String,
     color: (f32, f32, f32),
-    fire_delay: u64,
+    pub fire_delay: u64,
+    // a bullet from each every shot, radians off the nose
+    pub guns: Vec<f32>,
     fire_reload: u64,
     fire_magazine: u32,
+    // energy the shield holds, drained per second while raised and per hit taken
+    pub shield_energy: f32,
+    pub shield_drain: f32,
+    pub shield_hit: f32,
+    // per second while lowered
+    pub shield_recharge: f32,
+    // grid units, rocks bounce off this rather than the hull
+    pub shield_radius: f32,
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+pub struct ShipConfig {
+    pub ships: Vec<ShipDef>,
+}
+
+impl ShipConfig {
+    // a mod may have shortened the roster, anything past the end flies the first ship
+    fn index(&self, kind: usize) -> usize {
+        if kind < self.ships.len() {
+            kind
+        } else {
+            0
+        }
+    }
+
+    pub fn ship(&self, kind: usize) -> Option<&ShipDef> {
+        self.ships.get(self.index(kind))
+    }
 }
 
 #[derive(Resource)]
-struct ShipConfigHandle {
-    config: Handle<ShipConfig>,
+pub struct ShipConfigHandle {
+    pub config: Handle<ShipConfig>,
 }
 
+// a mesh and material for each ship in the roster, in the same order
 #[derive(Resource)]
-struct ShipAsset {
-    mesh: Handle<Mesh>,
-    material: Handle<ColorMaterial>,
+pub struct ShipAsset {
+    meshes: Vec<Handle<Mesh>>,
+    materials: Vec<Handle<ColorMaterial>>,
 }
 
 fn load_config(
@@ -57,18 +93,27 @@ fn load_assets(
         return;
     }
     if let Some(config) = configs.get(config_handle.config.id()) {
-        let mesh: Handle<Mesh> = asset_server.load(
-            GltfAssetLabel::Primitive {
-                mesh: 0,
-                primitive: 0,
-            }
-            .from_asset(config.mesh_path.clone()),
-        );
-        let color = Color::srgb(config.color.0, config.color.1, config.color.2);
-        let material = materials.add(color);
+        let meshes = config
+            .ships
+            .iter()
+            .map(|ship| {
+                asset_server.load(
+                    GltfAssetLabel::Primitive {
+                        mesh: 0,
+                        primitive: 0,
+                    }
+                    .from_asset(ship.mesh_path.clone()),
+                )
+            })
+            .collect();
+        let materials = config
+            .ships
+            .iter()
+            .map(|ship| materials.add(Color::srgb(ship.color.0, ship.color.1, ship.color.2)))
+            .collect();
         commands.insert_resource(ShipAsset {
-            mesh,
-            material,
+            meshes,
+            materials,
         });
     }
 }
@@ -77,9 +122,48 @@ fn load_assets(
 #[derive(Component)]
 pub struct Ship;
 
+// which ship in the roster this is
+#[derive(Component, Clone, Copy, Default)]
+pub struct ShipKind(pub usize);
+
+// a controller that gets a ship at the start of every game
+#[derive(Component, Clone, Copy)]
+pub struct Pilot {
+    pub team: u32,
+    pub spawn: Vec2,
+    // index into the roster
+    pub ship: usize,
+}
+
+impl Default for Pilot {
+    fn default() -> Self {
+        Self {
+            team: 1,
+            spawn: Vec2::ZERO,
+            ship: 0,
+        }
+    }
+}
+
+// radians off the heading where twin stick steering starts easing off the turn
+const HEADING_EASE: f32 = 0.5;
+
+// nothing hurts ships, toggled from the console
+#[derive(Resource, Default)]
+pub struct Invulnerable(pub bool);
+
+#[derive(Event)]
+pub struct ShipDestroyed {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub rotation: f32,
+    pub scale: f32,
+}
+
 #[derive(Bundle)]
 struct ShipBundle {
     ship: Ship,
+    kind: ShipKind,
     pawn: ShipPawn,
     position: Position,
     rotation: Rotation,
@@ -96,9 +180,10 @@ struct ShipBundle {
 }
 
 impl ShipBundle {
-    fn new(x: f32, y: f32, pawn: ShipPawn) -> Self {
+    fn new(x: f32, y: f32, pawn: ShipPawn, team: u32, kind: usize) -> Self {
         Self {
             ship: Ship,
+            kind: ShipKind(kind),
             pawn,
             position: Position(Vec2::new(x, y)),
             rotation: Rotation(0.0),
@@ -114,38 +199,96 @@ impl ShipBundle {
                 radius: 0.1,
                 mass: 2.0,
             },
-            collider: Collider { team: 1 },
+            collider: Collider { team },
         }
     }
 }
 
 
-fn spawn_ship(
+type AnyController = Or<(With<Pilot>, With<PlayerController>)>;
+
+// other modes set up their own controllers before startup, otherwise it's one local player
+fn spawn_player(mut commands: Commands, controllers: Query<(), AnyController>) {
+    if controllers.is_empty() {
+        commands.spawn((PlayerController { id: 0 }, Pilot::default()));
+    }
+}
+
+pub fn spawn_ship(
     mut commands: Commands,
+    pilots: Query<(Entity, &Pilot)>,
 ) {
-    let player_entity = commands.spawn(PlayerController { id: 0 }).id();
+    for (controller, pilot) in pilots.iter() {
+        commands.spawn((
+            ShipBundle::new(
+                pilot.spawn.x,
+                pilot.spawn.y,
+                ShipPawn::new(controller),
+                pilot.team,
+                pilot.ship,
+            ),
+            NeedsConfig,
+            Transform::default(),
+        ));
+    }
+}
 
-    commands.spawn((
-        ShipBundle::new(0., 0., ShipPawn::new(player_entity)),
-        NeedsConfig,
-        Transform::default(),
-    ));
+// rebuild a saved ship, add_config fills in the mesh and handling
+pub fn restore_ship(
+    world: &mut World,
+    body: &BodyState,
+    controller: Entity,
+    team: u32,
+    kind: usize,
+    last_shot: Duration,
+) -> Entity {
+    let mut bundle = ShipBundle::new(
+        body.position.x,
+        body.position.y,
+        ShipPawn::new(controller),
+        team,
+        kind,
+    );
+    bundle.velocity = Velocity(body.velocity);
+    bundle.rotation = Rotation(body.rotation);
+    bundle.angular_velocity = AngularVelocity(body.angular_velocity);
+    bundle.scale = Scale(body.scale);
+    bundle.last_shot = TimeStamp(last_shot);
+    world.spawn((bundle, NeedsConfig, Transform::default())).id()
 }
 
+type UnconfiguredShip<'a> = (
+    Entity,
+    &'a ShipKind,
+    &'a mut Damping,
+    &'a mut AngularDamping,
+    &'a mut RigidBody,
+);
+
+// runs behind the ship select screen too, so a new pick shows straight away
 fn add_config(
     mut commands: Commands,
-    mut ships: Query<(Entity, &mut Damping, &mut AngularDamping, &mut NeedsConfig), With<Ship>>,
+    mut ships: Query<UnconfiguredShip, (With<Ship>, With<NeedsConfig>)>,
     ship_assets: Option<Res<ShipAsset>>,
     configs: Res<Assets<ShipConfig>>,
     config_handle: Res<ShipConfigHandle>,
 ) {
     if let Some(assets) = ship_assets {
         if let Some(config) = configs.get(config_handle.config.id()) {
-            for (entity, mut damping, mut angular_damping, _) in ships.iter_mut() {
-                damping.0 = config.damping;
-                angular_damping.0 = config.damping_angular;
-                commands.entity(entity).insert(Mesh2d(assets.mesh.clone()));
-                commands.entity(entity).insert(MeshMaterial2d(assets.material.clone()));
+            for (entity, kind, mut damping, mut angular_damping, mut body) in ships.iter_mut() {
+                let index = config.index(kind.0);
+                let (Some(ship), Some(mesh), Some(material)) = (
+                    config.ships.get(index),
+                    assets.meshes.get(index),
+                    assets.materials.get(index),
+                ) else {
+                    continue;
+                };
+                damping.0 = ship.damping;
+                angular_damping.0 = ship.damping_angular;
+                body.radius = ship.radius;
+                commands.entity(entity).insert(Mesh2d(mesh.clone()));
+                commands.entity(entity).insert(MeshMaterial2d(material.clone()));
                 commands.entity(entity).remove::<NeedsConfig>();
             }
         }
@@ -155,20 +298,20 @@ fn add_config(
 fn apply_accel(
     configs: Res<Assets<ShipConfig>>,
     config_handle: Res<ShipConfigHandle>,
-    mut ships: Query<(&mut Acceleration, &ShipPawn), With<Ship>>,
+    mut ships: Query<(&mut Acceleration, &ShipPawn, &ShipKind), With<Ship>>,
     mut events: EventReader<InputEvent>,
 ) {
     // Reset acceleration for all ships when no events are present
-    for (mut acceleration, _) in ships.iter_mut() {
+    for (mut acceleration, _, _) in ships.iter_mut() {
         acceleration.0 = Vec2::ZERO;
     }
 
     for event in events.read() {
         if event.input.action == Actions::MoveForward {
-            for (mut acceleration, pawn) in ships.iter_mut() {
-                if let Some(config) = configs.get(config_handle.config.id()) {
+            for (mut acceleration, pawn, kind) in ships.iter_mut() {
+                if let Some(ship) = configs.get(config_handle.config.id()).and_then(|config| config.ship(kind.0)) {
                     if pawn.get_controller() == &event.controller {
-                        acceleration.0 = Vec2::new(0.0, config.speed * event.input.value);
+                        acceleration.0 = Vec2::new(0.0, ship.speed * event.input.value);
                     }
                 }
             }
@@ -179,20 +322,28 @@ fn apply_accel(
 fn apply_accel_ang(
     configs: Res<Assets<ShipConfig>>,
     config_handle: Res<ShipConfigHandle>,
-    mut ships: Query<(&mut AngularAcceleration, &ShipPawn), With<Ship>>,
+    mut ships: Query<(&mut AngularAcceleration, &Rotation, &ShipPawn, &ShipKind), With<Ship>>,
     mut events: EventReader<InputEvent>,
 ) {
     //reset all angular acceleration
-    for (mut angular_accel, _) in ships.iter_mut() {
+    for (mut angular_accel, _, _, _) in ships.iter_mut() {
         angular_accel.0 = 0.0;
     }
 
     for event in events.read() {
-        if event.input.action == Actions::Rotate {
-            for (mut angular_accel, pawn) in ships.iter_mut() {
-                if let Some(config) = configs.get(config_handle.config.id()) {
+        if event.input.action == Actions::Rotate || event.input.action == Actions::Heading {
+            for (mut angular_accel, rotation, pawn, kind) in ships.iter_mut() {
+                if let Some(ship) = configs.get(config_handle.config.id()).and_then(|config| config.ship(kind.0)) {
                     if pawn.get_controller() == &event.controller {
-                        angular_accel.0 = config.speed_angular * event.input.value;
+                        let turn = match event.input.action {
+                            // full turn until it's close, then ease in so it doesn't swing past
+                            Actions::Heading => {
+                                let error = turn_between(rotation.0, event.input.value);
+                                (error / HEADING_EASE).clamp(-1.0, 1.0)
+                            }
+                            _ => event.input.value,
+                        };
+                        angular_accel.0 = ship.speed_angular * turn;
                     }
                 }
             }
@@ -200,36 +351,57 @@ fn apply_accel_ang(
     }
 }
 
+type Gunship<'a> = (
+    &'a Position,
+    &'a Rotation,
+    &'a mut TimeStamp,
+    &'a ShipPawn,
+    &'a Collider,
+    &'a ShipKind,
+    Option<&'a RapidFire>,
+    Option<&'a SpreadShot>,
+);
+
 //TODO! add magazine
 fn shoot(
     time: Res<Time>,
-    mut ships: Query<
-        (
-            &Position,
-            &Rotation,
-            &mut TimeStamp,
-            &ShipPawn
-        ),
-        With<Ship>,
-    >,
+    mut ships: Query<Gunship, With<Ship>>,
     mut events: EventReader<InputEvent>,
     mut create_bullet: EventWriter<CreateBullet>,
     configs: Res<Assets<ShipConfig>>,
     config_handle: Res<ShipConfigHandle>,
 ) {
     for event in events.read() {
-        if event.input.action == Actions::Shoot {
-            for (position, rotation, mut last_shot_time, pawn) in ships.iter_mut() {
-                if let Some(config) = configs.get(config_handle.config.id()) {
-                    if pawn.get_controller() == &event.controller {
-                        let time_elapsed = time.elapsed();
-                        if time_elapsed - last_shot_time.0 > Duration::from_millis(config.fire_delay) {
-                            create_bullet.send(CreateBullet {
-                                position: position.0,
-                                rotation: rotation.0,
-                            });
-                            last_shot_time.0 = time_elapsed;
+        // twin stick aims on its own, the hull can point anywhere
+        let aim = match event.input.action {
+            Actions::Shoot => None,
+            Actions::Aim => Some(event.input.value),
+            _ => continue,
+        };
+        for (position, rotation, mut last_shot_time, pawn, collider, kind, rapid_fire, spread_shot) in ships.iter_mut() {
+            if let Some(ship) = configs.get(config_handle.config.id()).and_then(|config| config.ship(kind.0)) {
+                if pawn.get_controller() == &event.controller {
+                    let time_elapsed = time.elapsed();
+                    let mut fire_delay = Duration::from_millis(ship.fire_delay);
+                    if let Some(rapid_fire) = rapid_fire {
+                        fire_delay = fire_delay.div_f32(rapid_fire.factor.max(1.0));
+                    }
+                    let spread = match spread_shot {
+                        Some(spread_shot) => vec![-spread_shot.angle, 0.0, spread_shot.angle],
+                        None => vec![0.0],
+                    };
+                    if time_elapsed - last_shot_time.0 > fire_delay {
+                        for gun in ship.guns.iter() {
+                            for offset in spread.iter() {
+                                create_bullet.send(CreateBullet {
+                                    position: position.0,
+                                    rotation: aim.unwrap_or(rotation.0) + gun + offset,
+                                    team: collider.team,
+                                    shooter: event.controller,
+                                });
+                            }
                         }
+                        last_shot_time.0 = time_elapsed;
                     }
                 }
             }
@@ -237,21 +409,66 @@ fn shoot(
     }
 }
 
+// whatever would end the game for a ship comes here first, true when a spare life saved it
+pub fn spend_life(commands: &mut Commands, ship: Entity, lives: Option<Mut<ExtraLives>>) -> bool {
+    let Some(mut lives) = lives.filter(|lives| lives.count > 0) else {
+        return false;
+    };
+    lives.count -= 1;
+    shield(&mut commands.entity(ship), lives.grace);
+    true
+}
+
+type Hittable<'a> = (
+    Entity,
+    &'a Collider,
+    &'a Position,
+    &'a Velocity,
+    &'a Rotation,
+    &'a Scale,
+    Has<Shielded>,
+    Has<Deflecting>,
+    Option<&'a mut ExtraLives>,
+);
+
 fn collisions_ship(
     mut commands: Commands,
-    ships: Query<(Entity, &Collider), With<Ship>>,
+    mut ships: Query<Hittable, With<Ship>>,
     colliders: Query<(Entity, &Collider)>,
     mut collisions: EventReader<Collision>,
+    mut destroyed: EventWriter<ShipDestroyed>,
     mut next_state: ResMut<NextState<GameState>>,
+    invulnerable: Res<Invulnerable>,
 ) {
+    if invulnerable.0 {
+        return;
+    }
+    // a spent life covers every other hit this frame too, the shield only lands next frame
+    let mut spared = Vec::new();
     for event in collisions.read() {
         for (entity_a, entity_b) in [
             (event.entity1, event.entity2),
             (event.entity2, event.entity1),
         ] {
-            if let Ok((ship, ship_collider)) = ships.get(entity_a) {
+            if let Ok((ship, ship_collider, position, velocity, rotation, scale, shielded, deflecting, lives)) =
+                ships.get_mut(entity_a)
+            {
                 if let Ok((_, collider)) = colliders.get(entity_b) {
-                    if collider.team != ship_collider.team {
+                    if collider.team != ship_collider.team && collider.team != PICKUP_TEAM {
+                        // a raised shield takes the hit, what it costs is up to the shield
+                        if shielded || deflecting || spared.contains(&ship) {
+                            continue;
+                        }
+                        if spend_life(&mut commands, ship, lives) {
+                            spared.push(ship);
+                            continue;
+                        }
+                        destroyed.send(ShipDestroyed {
+                            position: position.0,
+                            velocity: velocity.0,
+                            rotation: rotation.0,
+                            scale: scale.0,
+                        });
                         commands.entity(ship).despawn();
                         next_state.set(GameState::GameOver);
                     }
@@ -261,12 +478,28 @@ fn collisions_ship(
     }
 }
 
+fn god_command(
+    In(_): In<Vec<String>>,
+    mut invulnerable: ResMut<Invulnerable>,
+    mut cheated: ResMut<Cheated>,
+) -> String {
+    invulnerable.0 = !invulnerable.0;
+    cheated.0 = true;
+    if invulnerable.0 {
+        "god mode on".to_string()
+    } else {
+        "god mode off".to_string()
+    }
+}
+
 pub struct ShipPlugin;
 
 impl Plugin for ShipPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RonAssetPlugin::<ShipConfig>::new(&["ship.ron"]));
-        app.add_systems(Startup, (load_config, spawn_ship));
+        app.add_config::<ShipConfig>(&["ship.ron"]);
+        app.add_event::<ShipDestroyed>();
+        app.init_resource::<Invulnerable>();
+        app.add_systems(Startup, (load_config, (spawn_player, spawn_ship).chain()));
         app.add_systems(OnExit(GameState::GameOver), spawn_ship);
         app.add_systems(Update, (load_assets, add_config ).in_set(InGameSet::LoadEntities));
         app.add_systems(
@@ -274,5 +507,6 @@ impl Plugin for ShipPlugin {
             (apply_accel, apply_accel_ang, shoot).in_set(InGameSet::UpdateEntities),
         );
         app.add_systems(Update, (collisions_ship).in_set(InGameSet::DespawnEntities));
+        app.add_console_command("god", "ships can't be destroyed, again to turn it off", god_command);
     }
 }
@@ -0,0 +1,119 @@
+use crate::bodies::NeedsConfig;
+use crate::control::{Pawn, ShipPawn};
+use crate::grid::Grid;
+use crate::input_actions::*;
+use crate::ship::{Pilot, ShipConfig, ShipConfigHandle, ShipKind};
+use crate::GameState;
+use bevy::prelude::*;
+
+// present when runs start on the ship select screen, restarting comes back to it
+#[derive(Resource)]
+pub struct ShipSelect;
+
+#[derive(Component)]
+struct ShipSelectMessage;
+
+fn start_on_select(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::ShipSelect);
+}
+
+fn spawn_message(mut commands: Commands, asset_server: Res<AssetServer>, grid: Option<Res<Grid>>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    // the grid may still be loading on the first run, the message moves once it's known
+    let text_height = grid.map_or(200.0, |grid| grid.height_half * grid.size / 2.0);
+    commands.spawn((
+        ShipSelectMessage,
+        Text2d::new(""),
+        TextFont {
+            font,
+            font_size: 28.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_translation(Vec3::new(0.0, text_height, 1.0)),
+    ));
+}
+
+fn despawn_message(mut commands: Commands, messages: Query<Entity, With<ShipSelectMessage>>) {
+    for entity in messages.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+// each pilot picks with its own controller, so replays pick the same way
+#[allow(clippy::too_many_arguments)]
+fn pick_ship(
+    mut commands: Commands,
+    mut events: EventReader<InputEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    configs: Res<Assets<ShipConfig>>,
+    config_handle: Res<ShipConfigHandle>,
+    mut pilots: Query<&mut Pilot>,
+    ships: Query<(Entity, &ShipPawn)>,
+    grid: Option<Res<Grid>>,
+    mut messages: Query<(&mut Text2d, &mut Transform), With<ShipSelectMessage>>,
+) {
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    let count = config.ships.len().max(1);
+
+    for event in events.read() {
+        let step = match event.input.action {
+            Actions::MenuPrevious => count - 1,
+            Actions::MenuNext => 1,
+            Actions::Confirm => {
+                next_state.set(GameState::InGame);
+                continue;
+            }
+            _ => continue,
+        };
+        let Ok(mut pilot) = pilots.get_mut(event.controller) else {
+            continue;
+        };
+        pilot.ship = (pilot.ship + step) % count;
+        // swap the waiting ship over, add_config fits it out again
+        for (ship, pawn) in ships.iter() {
+            if pawn.get_controller() == &event.controller {
+                commands.entity(ship).insert((ShipKind(pilot.ship), NeedsConfig));
+            }
+        }
+    }
+
+    let Some(picked) = pilots.iter().next().and_then(|pilot| config.ship(pilot.ship)) else {
+        return;
+    };
+    let guns = match picked.guns.len() {
+        1 => "1 gun".to_string(),
+        guns => format!("{} guns", guns),
+    };
+    let shown = format!(
+        "< {} >\nthrust {}  turn {}  {}  {} shots/s\nLeft/Right to choose, Enter to launch",
+        picked.name,
+        picked.speed,
+        picked.speed_angular,
+        guns,
+        1000 / picked.fire_delay.max(1),
+    );
+    for (mut text, mut transform) in messages.iter_mut() {
+        if text.0 != shown {
+            text.0.clone_from(&shown);
+        }
+        if let Some(grid) = grid.as_ref() {
+            transform.translation.y = grid.height_half * grid.size / 2.0;
+        }
+    }
+}
+
+// runs begin by picking a ship from the roster, for local play
+pub struct ShipSelectPlugin;
+
+impl Plugin for ShipSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ShipSelect);
+        app.add_systems(Startup, start_on_select);
+        app.add_systems(OnEnter(GameState::ShipSelect), spawn_message);
+        app.add_systems(OnExit(GameState::ShipSelect), despawn_message);
+        app.add_systems(Update, pick_ship.run_if(in_state(GameState::ShipSelect)));
+    }
+}
@@ -1,24 +1,41 @@
+use crate::daily::DailyRun;
+use crate::grid::Grid;
 use crate::schedule::InGameSet;
 use crate::input_actions::*;
+use crate::ship_select::ShipSelect;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component, Debug)]
 struct RestartMessage;
 
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+#[derive(Component, Debug)]
+struct PauseMessage;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States, Serialize, Deserialize)]
 pub enum GameState {
     #[default]
     InGame,
     Paused,
     GameOver,
+    // picking from the roster before a run, only when ShipSelectPlugin is added
+    ShipSelect,
 }
 
+// present when another mode decides when to pause and restart, netplay has to agree with its peer
+#[derive(Resource)]
+pub struct StatesLocked;
+
 fn pause_system(
     mut next_state: ResMut<NextState<GameState>>,
     state: Res<State<GameState>>,
     mut events: EventReader<InputEvent>,
+    locked: Option<Res<StatesLocked>>,
 ) {
     for event in events.read() {
+        if locked.is_some() {
+            continue;
+        }
         if event.input.action == Actions::Pause {
             match state.get() {
                 GameState::InGame => {
@@ -37,10 +54,18 @@ fn restart_game(
     mut next_state: ResMut<NextState<GameState>>,
     state: Res<State<GameState>>,
     mut events: EventReader<InputEvent>,
+    locked: Option<Res<StatesLocked>>,
+    select: Option<Res<ShipSelect>>,
 ) {
     for event in events.read() {
+        if locked.is_some() {
+            continue;
+        }
         if event.input.action == Actions::Restart {
             match state.get() {
+                GameState::GameOver if select.is_some() => {
+                    next_state.set(GameState::ShipSelect);
+                }
                 GameState::GameOver => {
                     next_state.set(GameState::InGame);
                 }
@@ -53,11 +78,10 @@ fn restart_game(
 fn spawn_restart_message(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    window: Query<&Window>,
+    grid: Option<Res<Grid>>,
 ) {
-    if let Ok(window) = window.get_single() {
-        let window_height = window.resolution.height();
-        let text_height = window_height / 4.0 - 18.0;
+    if let Some(grid) = grid {
+        let text_height = grid.height_half * grid.size / 2.0 - 18.0;
 
         let font = asset_server.load("fonts/FiraMono-Medium.ttf");
         let text_font = TextFont {
@@ -82,6 +106,36 @@ fn despawn_restart_message(mut commands: Commands, messages: Query<Entity, With<
     }
 }
 
+fn spawn_pause_message(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    daily: Option<Res<DailyRun>>,
+) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    let text_font = TextFont {
+        font,
+        font_size: 36.0,
+        ..default()
+    };
+
+    commands.spawn((
+        PauseMessage,
+        Text2d::new(match daily {
+            Some(_) => "Paused",
+            None => "Paused\nS to Save  L to Load",
+        }),
+        text_font.clone(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_translation(Vec3::new(0.0, 0.0, 1.0)),
+    ));
+}
+
+fn despawn_pause_message(mut commands: Commands, messages: Query<Entity, With<PauseMessage>>) {
+    for entity in messages.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
 pub struct StatePlugin;
 
 impl Plugin for StatePlugin {
@@ -93,5 +147,7 @@ impl Plugin for StatePlugin {
         );
         app.add_systems(OnEnter(GameState::GameOver), spawn_restart_message);
         app.add_systems(OnExit(GameState::GameOver), despawn_restart_message);
+        app.add_systems(OnEnter(GameState::Paused), spawn_pause_message);
+        app.add_systems(OnExit(GameState::Paused), despawn_pause_message);
     }
 }
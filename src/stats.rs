@@ -0,0 +1,153 @@
+use crate::asteroid::{Asteroid, AsteroidConfig, AsteroidConfigHandle};
+use crate::bodies::*;
+use crate::bullet::{Bullet, CreateBullet};
+use crate::grid::Grid;
+use crate::schedule::InGameSet;
+use crate::score::Scored;
+use crate::ship::Ship;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+
+// the summary goes under the final ranking, a size smaller
+const FONT_SIZE: f32 = 24.0;
+
+// everything that happened this run, for the game over screen
+// saved and rolled back with the rest of the world, there are no waves so no wave reached
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RunStats {
+    pub time: Duration,
+    // by the asteroid sizes in a.ast.ron
+    pub destroyed: Vec<u32>,
+    pub shots: u32,
+    // bullets that hit something, however many asteroids they touched doing it
+    pub hits: u32,
+    // grid units, every ship together
+    pub distance: f32,
+}
+
+impl RunStats {
+    pub fn accuracy(&self) -> f32 {
+        if self.shots == 0 {
+            return 0.0;
+        }
+        self.hits as f32 / self.shots as f32
+    }
+}
+
+#[derive(Component)]
+struct Summary;
+
+fn track_flight(
+    time: Res<Time>,
+    mut stats: ResMut<RunStats>,
+    ships: Query<&Velocity, With<Ship>>,
+) {
+    stats.time += time.delta();
+    let travelled: f32 = ships.iter().map(|velocity| velocity.0.length()).sum();
+    stats.distance += travelled * time.delta_secs();
+}
+
+fn count_shots(mut stats: ResMut<RunStats>, mut events: EventReader<CreateBullet>) {
+    stats.shots += events.read().count() as u32;
+}
+
+fn count_hits(
+    mut stats: ResMut<RunStats>,
+    mut collisions: EventReader<Collision>,
+    bullets: Query<&Collider, With<Bullet>>,
+    asteroids: Query<&Collider, With<Asteroid>>,
+) {
+    let mut hit = HashSet::new();
+    for event in collisions.read() {
+        for (entity_a, entity_b) in [
+            (event.entity1, event.entity2),
+            (event.entity2, event.entity1),
+        ] {
+            if let (Ok(bullet), Ok(asteroid)) = (bullets.get(entity_a), asteroids.get(entity_b)) {
+                if bullet.team != asteroid.team {
+                    hit.insert(entity_a);
+                }
+            }
+        }
+    }
+    stats.hits += hit.len() as u32;
+}
+
+fn count_destroyed(mut stats: ResMut<RunStats>, mut events: EventReader<Scored>) {
+    for size in events.read().filter_map(|event| event.size) {
+        if stats.destroyed.len() <= size {
+            stats.destroyed.resize(size + 1, 0);
+        }
+        stats.destroyed[size] += 1;
+    }
+}
+
+fn spawn_summary(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stats: Res<RunStats>,
+    grid: Option<Res<Grid>>,
+    configs: Res<Assets<AsteroidConfig>>,
+    config_handle: Res<AsteroidConfigHandle>,
+) {
+    let (Some(grid), Some(config)) = (grid, configs.get(config_handle.config.id())) else {
+        return;
+    };
+    let seconds = stats.time.as_secs();
+    let destroyed: Vec<String> = config
+        .sizes
+        .iter()
+        .enumerate()
+        .map(|(index, size)| format!("{} {}", stats.destroyed.get(index).copied().unwrap_or(0), size.name))
+        .collect();
+    let summary = format!(
+        "survived {}:{:02}\ndestroyed {}\nshots {}  accuracy {:.0}%\ntravelled {:.1}",
+        seconds / 60,
+        seconds % 60,
+        destroyed.join("  "),
+        stats.shots,
+        stats.accuracy() * 100.0,
+        stats.distance,
+    );
+
+    commands.spawn((
+        Summary,
+        Text2d::new(summary),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: FONT_SIZE,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_translation(Vec3::new(0.0, -grid.height_half * grid.size / 2.0, 1.0)),
+    ));
+}
+
+fn despawn_summary(mut commands: Commands, summaries: Query<Entity, With<Summary>>) {
+    for entity in summaries.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn reset_stats(mut stats: ResMut<RunStats>) {
+    *stats = RunStats::default();
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>();
+        app.add_systems(Update, (track_flight).in_set(InGameSet::UpdateEntities));
+        app.add_systems(
+            Update,
+            (count_shots, count_hits, count_destroyed).in_set(InGameSet::CollisionReaction),
+        );
+        app.add_systems(OnEnter(GameState::GameOver), spawn_summary);
+        // a rollback that takes back a game over restores the stats after this
+        app.add_systems(OnExit(GameState::GameOver), (despawn_summary, reset_stats));
+    }
+}
@@ -0,0 +1,336 @@
+use crate::console::ConfigExt;
+use crate::schedule::InGameSet;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+
+// what a touch binding reads, bound to actions in a.input.ron
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TouchControl {
+    // the virtual stick, right and up are positive
+    StickX,
+    StickY,
+    // index into the buttons in a.touch.ron
+    Button(usize),
+}
+
+#[derive(Deserialize, Clone)]
+struct StickDef {
+    // fractions of the window from the top left
+    center: (f32, f32),
+    // pixels, how far the knob goes
+    radius: f32,
+    // fraction of the radius that counts as rest
+    dead_zone: f32,
+}
+
+#[derive(Deserialize, Clone)]
+struct ButtonDef {
+    label: String,
+    center: (f32, f32),
+    radius: f32,
+}
+
+#[derive(Deserialize, Asset, Clone, TypePath)]
+struct TouchConfig {
+    stick: StickDef,
+    buttons: Vec<ButtonDef>,
+    // a finger landing within this many stick radii of the center grabs it
+    grab: f32,
+    font_size: f32,
+}
+
+#[derive(Resource)]
+struct TouchConfigHandle {
+    config: Handle<TouchConfig>,
+}
+
+// where every finger left the controls this frame, read by the input bindings
+#[derive(Resource, Default)]
+pub struct TouchState {
+    stick: Vec2,
+    // the finger holding the stick, it keeps it until lifted wherever it wanders
+    stick_finger: Option<u64>,
+    buttons: Vec<bool>,
+    // the overlay only goes up once somebody touches the screen
+    seen: bool,
+}
+
+impl TouchState {
+    pub fn value(&self, control: TouchControl) -> Option<f32> {
+        match control {
+            TouchControl::StickX => (self.stick.x != 0.0).then_some(self.stick.x),
+            TouchControl::StickY => (self.stick.y != 0.0).then_some(self.stick.y),
+            TouchControl::Button(index) => self.buttons.get(index).copied().unwrap_or(false).then_some(1.0),
+        }
+    }
+}
+
+#[derive(Component)]
+struct TouchOverlay;
+
+#[derive(Component)]
+struct StickBase;
+
+#[derive(Component)]
+struct StickKnob;
+
+#[derive(Component)]
+struct TouchButton(usize);
+
+fn load_config(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let config = asset_server.load("a.touch.ron");
+    commands.insert_resource(TouchConfigHandle { config });
+}
+
+fn window_point(center: (f32, f32), window: &Window) -> Vec2 {
+    Vec2::new(center.0 * window.width(), center.1 * window.height())
+}
+
+// the finger holding the stick keeps it until lifted, otherwise the first to land within reach takes it
+fn claim_stick(
+    held: Option<u64>,
+    pressed: &[u64],
+    landed: &[(u64, Vec2)],
+    center: Vec2,
+    reach: f32,
+) -> Option<u64> {
+    match held {
+        Some(finger) if pressed.contains(&finger) => Some(finger),
+        _ => landed
+            .iter()
+            .find(|(_, position)| position.distance(center) < reach)
+            .map(|(finger, _)| *finger),
+    }
+}
+
+// right and up are positive, clamped to the rim, zero until it leaves the dead zone
+fn stick_offset(position: Vec2, center: Vec2, radius: f32, dead_zone: f32) -> Vec2 {
+    // screen y grows downward, the stick's grows up
+    let offset = (position - center) * Vec2::new(1.0, -1.0) / radius;
+    let offset = offset.clamp_length_max(1.0);
+    if offset.length() > dead_zone {
+        offset
+    } else {
+        Vec2::ZERO
+    }
+}
+
+// every finger at once, one on the stick and any number on the buttons
+fn read_touches(
+    touches: Res<Touches>,
+    mut state: ResMut<TouchState>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    configs: Res<Assets<TouchConfig>>,
+    config_handle: Res<TouchConfigHandle>,
+) {
+    let (Ok(window), Some(config)) = (windows.get_single(), configs.get(config_handle.config.id())) else {
+        return;
+    };
+    if touches.any_just_pressed() {
+        state.seen = true;
+    }
+
+    let stick_center = window_point(config.stick.center, window);
+    let radius = config.stick.radius.max(1.0);
+    let pressed: Vec<u64> = touches.iter().map(|touch| touch.id()).collect();
+    let landed: Vec<(u64, Vec2)> = touches
+        .iter_just_pressed()
+        .map(|touch| (touch.id(), touch.position()))
+        .collect();
+    state.stick_finger = claim_stick(
+        state.stick_finger,
+        &pressed,
+        &landed,
+        stick_center,
+        radius * config.grab,
+    );
+
+    state.stick = state
+        .stick_finger
+        .and_then(|finger| touches.get_pressed(finger))
+        .map_or(Vec2::ZERO, |touch| {
+            stick_offset(touch.position(), stick_center, radius, config.stick.dead_zone)
+        });
+
+    let stick_finger = state.stick_finger;
+    state.buttons = config
+        .buttons
+        .iter()
+        .map(|button| {
+            let center = window_point(button.center, window);
+            touches
+                .iter()
+                .filter(|touch| Some(touch.id()) != stick_finger)
+                .any(|touch| touch.position().distance(center) < button.radius)
+        })
+        .collect();
+}
+
+fn circle(center: Vec2, radius: f32) -> Node {
+    Node {
+        position_type: PositionType::Absolute,
+        left: Val::Px(center.x - radius),
+        top: Val::Px(center.y - radius),
+        width: Val::Px(radius * 2.0),
+        height: Val::Px(radius * 2.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    }
+}
+
+fn spawn_overlay(
+    mut commands: Commands,
+    state: Res<TouchState>,
+    overlays: Query<(), With<TouchOverlay>>,
+    configs: Res<Assets<TouchConfig>>,
+    config_handle: Res<TouchConfigHandle>,
+) {
+    if !state.seen || !overlays.is_empty() {
+        return;
+    }
+    let Some(config) = configs.get(config_handle.config.id()) else {
+        return;
+    };
+    // laid out properly by place_overlay once it knows the window
+    commands.spawn((
+        TouchOverlay,
+        StickBase,
+        circle(Vec2::ZERO, config.stick.radius),
+        BorderRadius::MAX,
+        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.1)),
+        GlobalZIndex(5),
+    ));
+    commands.spawn((
+        TouchOverlay,
+        StickKnob,
+        circle(Vec2::ZERO, config.stick.radius / 2.0),
+        BorderRadius::MAX,
+        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.25)),
+        GlobalZIndex(6),
+    ));
+    for (index, button) in config.buttons.iter().enumerate() {
+        commands
+            .spawn((
+                TouchOverlay,
+                TouchButton(index),
+                circle(Vec2::ZERO, button.radius),
+                BorderRadius::MAX,
+                BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.1)),
+                GlobalZIndex(5),
+            ))
+            .with_child((
+                Text::new(button.label.clone()),
+                TextFont {
+                    font_size: config.font_size,
+                    ..default()
+                },
+            ));
+    }
+}
+
+type OverlayPart<'a> = (
+    &'a mut Node,
+    &'a mut BackgroundColor,
+    Has<StickBase>,
+    Has<StickKnob>,
+    Option<&'a TouchButton>,
+);
+
+// follows the window size and lights up whatever is held
+fn place_overlay(
+    state: Res<TouchState>,
+    mut parts: Query<OverlayPart, With<TouchOverlay>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    configs: Res<Assets<TouchConfig>>,
+    config_handle: Res<TouchConfigHandle>,
+) {
+    let (Ok(window), Some(config)) = (windows.get_single(), configs.get(config_handle.config.id())) else {
+        return;
+    };
+    let stick_center = window_point(config.stick.center, window);
+    for (mut node, mut color, base, knob, button) in parts.iter_mut() {
+        let (center, radius, held) = if base {
+            (stick_center, config.stick.radius, state.stick_finger.is_some())
+        } else if knob {
+            let offset = state.stick * Vec2::new(1.0, -1.0) * config.stick.radius;
+            (stick_center + offset, config.stick.radius / 2.0, state.stick_finger.is_some())
+        } else if let Some(TouchButton(index)) = button {
+            let Some(def) = config.buttons.get(*index) else {
+                continue;
+            };
+            let held = state.buttons.get(*index).copied().unwrap_or(false);
+            (window_point(def.center, window), def.radius, held)
+        } else {
+            continue;
+        };
+        let placed = circle(center, radius);
+        if *node != placed {
+            *node = placed;
+        }
+        color.0 = Color::srgba(1.0, 1.0, 1.0, if held { 0.3 } else { 0.1 });
+    }
+}
+
+// an on screen stick and buttons for phones, the bindings map them to actions like any other input
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_config::<TouchConfig>(&["touch.ron"]);
+        app.init_resource::<TouchState>();
+        app.add_systems(Startup, load_config);
+        app.add_systems(Update, (read_touches).before(InGameSet::GameInput));
+        app.add_systems(Update, (spawn_overlay, place_overlay).chain());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: Vec2 = Vec2::new(100.0, 400.0);
+
+    #[test]
+    fn offset_is_zero_in_the_dead_zone() {
+        let offset = stick_offset(CENTER + Vec2::new(10.0, 0.0), CENTER, 50.0, 0.25);
+        assert_eq!(offset, Vec2::ZERO);
+        let offset = stick_offset(CENTER + Vec2::new(20.0, 0.0), CENTER, 50.0, 0.25);
+        assert!((offset.x - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn offset_points_up_when_the_finger_moves_up_the_screen() {
+        let offset = stick_offset(CENTER + Vec2::new(0.0, -25.0), CENTER, 50.0, 0.1);
+        assert!((offset.y - 0.5).abs() < 1e-6);
+        assert_eq!(offset.x, 0.0);
+    }
+
+    #[test]
+    fn offset_is_clamped_to_the_rim() {
+        let offset = stick_offset(CENTER + Vec2::new(300.0, 400.0), CENTER, 50.0, 0.1);
+        assert!((offset.length() - 1.0).abs() < 1e-6);
+        assert!((offset - Vec2::new(0.6, -0.8)).length() < 1e-6);
+    }
+
+    #[test]
+    fn held_finger_keeps_the_stick_wherever_it_wanders() {
+        let landed = [(2, CENTER)];
+        assert_eq!(claim_stick(Some(1), &[1, 2], &landed, CENTER, 75.0), Some(1));
+    }
+
+    #[test]
+    fn lifted_finger_lets_a_new_one_grab() {
+        let landed = [(3, CENTER + Vec2::new(500.0, 0.0)), (2, CENTER + Vec2::new(30.0, 0.0))];
+        assert_eq!(claim_stick(Some(1), &[2, 3], &landed, CENTER, 75.0), Some(2));
+    }
+
+    #[test]
+    fn fingers_out_of_reach_or_already_down_dont_grab() {
+        let far = [(2, CENTER + Vec2::new(80.0, 0.0))];
+        assert_eq!(claim_stick(None, &[2], &far, CENTER, 75.0), None);
+        // only fingers that just landed, sliding onto the stick doesn't take it
+        assert_eq!(claim_stick(None, &[4], &[], CENTER, 75.0), None);
+    }
+}